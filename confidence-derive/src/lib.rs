@@ -0,0 +1,192 @@
+//! Derive macros for the Confidence SDK for Rust.
+//!
+//! Use them through the `derive` feature of `spotify_confidence_sdk` rather than depending on
+//! this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, LitStr};
+
+/// Derive `TypeConversionTrait` for a struct with named fields, and `ConfidenceFlag` when the
+/// struct is annotated with `#[confidence(flag = "...")]`.
+///
+/// Every field is read from the resolved value under its own name, or the one given with
+/// `#[confidence(rename = "...")]`. A field missing from the resolved value, or left unset by the
+/// variant, falls back to `#[confidence(default = ...)]`, or to `Default::default()` when no
+/// default is given.
+#[proc_macro_derive(ConfidenceFlag, attributes(confidence))]
+pub fn derive_confidence_flag(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct FlagField {
+    ident: syn::Ident,
+    ty: syn::Type,
+    key: String,
+    default: Option<Expr>,
+}
+
+impl FlagField {
+    fn default_value(&self) -> TokenStream2 {
+        let ty = &self.ty;
+        match &self.default {
+            Some(default) => quote! { ::core::convert::Into::<#ty>::into(#default) },
+            None => quote! { <#ty as ::core::default::Default>::default() },
+        }
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let flag = flag_name(&input)?;
+    let fields = flag_fields(&input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let read_fields = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let key = &field.key;
+        let default = field.default_value();
+        quote! {
+            #ident: match fields.get(#key) {
+                ::core::option::Option::Some(value) if !value.is_null() => {
+                    <#ty as ::spotify_confidence_sdk::confidence_value::TypeConversionTrait>::process(value)?
+                }
+                _ => #default,
+            }
+        }
+    });
+
+    let find_mismatch = fields.iter().map(|field| {
+        let ty = &field.ty;
+        let key = &field.key;
+        quote! {
+            if let ::core::option::Option::Some(value) = fields.get(#key) {
+                if !value.is_null()
+                    && <#ty as ::spotify_confidence_sdk::confidence_value::TypeConversionTrait>::process(value).is_none()
+                {
+                    return <#ty as ::spotify_confidence_sdk::confidence_value::TypeConversionTrait>::mismatch(value)
+                        .in_field(#key);
+                }
+            }
+        }
+    });
+
+    let conversion = quote! {
+        impl #impl_generics ::spotify_confidence_sdk::confidence_value::TypeConversionTrait
+            for #name #ty_generics #where_clause
+        {
+            fn type_name() -> ::std::string::String {
+                ::std::string::String::from("struct")
+            }
+
+            fn process(
+                value: &::spotify_confidence_sdk::ConfidenceValue,
+            ) -> ::core::option::Option<Self> {
+                let fields = &value.as_struct()?.fields;
+                ::core::option::Option::Some(Self { #(#read_fields,)* })
+            }
+
+            fn mismatch(
+                value: &::spotify_confidence_sdk::ConfidenceValue,
+            ) -> ::spotify_confidence_sdk::confidence_value::TypeMismatch {
+                if let ::core::option::Option::Some(value) = value.as_struct() {
+                    let fields = &value.fields;
+                    #(#find_mismatch)*
+                }
+                ::spotify_confidence_sdk::confidence_value::TypeMismatch::new(
+                    <Self as ::spotify_confidence_sdk::confidence_value::TypeConversionTrait>::type_name(),
+                    value,
+                )
+            }
+        }
+    };
+
+    let Some(flag) = flag else {
+        return Ok(conversion);
+    };
+    let default_fields = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let default = field.default_value();
+        quote! { #ident: #default }
+    });
+
+    Ok(quote! {
+        #conversion
+
+        impl #impl_generics ::spotify_confidence_sdk::ConfidenceFlag for #name #ty_generics #where_clause {
+            const FLAG_NAME: &'static str = #flag;
+
+            fn default_value() -> Self {
+                Self { #(#default_fields,)* }
+            }
+        }
+    })
+}
+
+fn flag_name(input: &DeriveInput) -> syn::Result<Option<LitStr>> {
+    let mut flag = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("confidence")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("flag") {
+                flag = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `flag = \"...\"`"))
+            }
+        })?;
+    }
+    Ok(flag)
+}
+
+fn flag_fields(input: &DeriveInput) -> syn::Result<Vec<FlagField>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ConfidenceFlag can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ConfidenceFlag can only be derived for structs",
+            ))
+        }
+    };
+
+    fields
+        .iter()
+        .map(|field| {
+            let ident = field.ident.clone().expect("named field");
+            let mut key = ident.to_string();
+            let mut default = None;
+            for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("confidence")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("rename") {
+                        key = meta.value()?.parse::<LitStr>()?.value();
+                        Ok(())
+                    } else if meta.path.is_ident("default") {
+                        default = Some(meta.value()?.parse::<Expr>()?);
+                        Ok(())
+                    } else {
+                        Err(meta.error("expected `rename = \"...\"` or `default = ...`"))
+                    }
+                })?;
+            }
+            Ok(FlagField {
+                ident,
+                ty: field.ty.clone(),
+                key,
+                default,
+            })
+        })
+        .collect()
+}
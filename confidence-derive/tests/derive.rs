@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use spotify_confidence_sdk::confidence_value::{StructValue, TypeConversionTrait};
+use spotify_confidence_sdk::details::EvaluationReason;
+use spotify_confidence_sdk::evaluation_error::EvaluationErrorCode;
+use spotify_confidence_sdk::models::{ResolvedFlag, ResolvedFlags};
+use spotify_confidence_sdk::resolve::MockNetworkFlagResolver;
+use spotify_confidence_sdk::{APIConfig, Confidence, ConfidenceFlag, ConfidenceValue};
+
+#[derive(ConfidenceFlag, Debug, PartialEq)]
+struct Banner {
+    text: String,
+    #[confidence(default = 1.0)]
+    opacity: f64,
+}
+
+#[derive(ConfidenceFlag, Debug, PartialEq)]
+#[confidence(flag = "checkout-redesign")]
+struct CheckoutRedesign {
+    #[confidence(default = true)]
+    enabled: bool,
+    #[confidence(rename = "max-items", default = 3)]
+    max_items: i64,
+    #[confidence(default = Banner { text: "Welcome".to_string(), opacity: 1.0 })]
+    banner: Banner,
+    title: String,
+}
+
+fn confidence_with(value: StructValue) -> Confidence {
+    let mut mock_resolver = MockNetworkFlagResolver::new();
+    mock_resolver.expect_resolve_with_apply().returning(move |_, flags, _, _| {
+        assert_eq!(flags, vec!["checkout-redesign".to_string()]);
+        let value = value.clone();
+        Box::pin(async move {
+            Ok(ResolvedFlags {
+                resolve_token: "token".to_string(),
+                flags: vec![ResolvedFlag {
+                    flag: "flags/checkout-redesign".to_string(),
+                    variant: "flags/checkout-redesign/variants/on".to_string(),
+                    value: Some(value),
+                    reason: "RESOLVE_REASON_MATCH".to_string(),
+                }],
+            })
+        })
+    });
+
+    Confidence::builder()
+        .api_config(APIConfig::default())
+        .resolver(Arc::new(mock_resolver))
+        .build()
+}
+
+#[test]
+fn default_value_uses_field_defaults() {
+    assert_eq!(CheckoutRedesign::FLAG_NAME, "checkout-redesign");
+    assert_eq!(
+        CheckoutRedesign::default_value(),
+        CheckoutRedesign {
+            enabled: true,
+            max_items: 3,
+            banner: Banner { text: "Welcome".to_string(), opacity: 1.0 },
+            title: String::new(),
+        }
+    );
+}
+
+#[test]
+fn missing_fields_fall_back_to_defaults() {
+    let value = ConfidenceValue::Struct(
+        StructValue::default()
+            .with_field("max-items", 5)
+            .with_field("enabled", ConfidenceValue::Null)
+            .with_field("banner", StructValue::default().with_field("text", "Hello")),
+    );
+
+    assert_eq!(
+        CheckoutRedesign::process(&value),
+        Some(CheckoutRedesign {
+            enabled: true,
+            max_items: 5,
+            banner: Banner { text: "Hello".to_string(), opacity: 1.0 },
+            title: String::new(),
+        })
+    );
+}
+
+#[test]
+fn mismatched_field_types_are_rejected() {
+    let value = ConfidenceValue::Struct(StructValue::default().with_field("enabled", "yes"));
+
+    assert_eq!(CheckoutRedesign::process(&value), None);
+    assert_eq!(CheckoutRedesign::process(&ConfidenceValue::Bool(true)), None);
+}
+
+#[tokio::test]
+async fn get_reads_the_whole_flag() {
+    let confidence = confidence_with(
+        StructValue::default()
+            .with_field("enabled", false)
+            .with_field("title", "New checkout"),
+    );
+
+    let details = confidence.get::<CheckoutRedesign>().await.unwrap();
+
+    assert_eq!(details.reason, Some(EvaluationReason::TargetingMatch));
+    assert!(!details.value.enabled);
+    assert_eq!(details.value.max_items, 3);
+    assert_eq!(details.value.title, "New checkout");
+}
+
+#[tokio::test]
+async fn get_reports_type_mismatch() {
+    let confidence = confidence_with(StructValue::default().with_field("max-items", "many"));
+
+    let error = confidence.get::<CheckoutRedesign>().await.unwrap_err();
+
+    assert_eq!(error.code, EvaluationErrorCode::TypeMismatch);
+    assert_eq!(
+        error.message.unwrap(),
+        "schema type is different for checkout-redesign.max-items: expected int, found string"
+    );
+
+    let confidence = confidence_with(
+        StructValue::default().with_field("banner", StructValue::default().with_field("opacity", true)),
+    );
+    let error = confidence.get::<CheckoutRedesign>().await.unwrap_err();
+    assert_eq!(
+        error.message.unwrap(),
+        "schema type is different for checkout-redesign.banner.opacity: expected float, found bool"
+    );
+}
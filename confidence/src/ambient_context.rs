@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::ConfidenceValue;
+
+tokio::task_local! {
+    static AMBIENT_CONTEXT: Arc<HashMap<String, ConfidenceValue>>;
+}
+
+/// Run `future` with `context` as the ambient context.
+///
+/// Resolves and events made inside the scope, from any [`crate::Confidence`], overlay the ambient
+/// context on the context of that instance. Scopes nest: an inner scope overlays its context on the
+/// outer one. The context follows the future across `.await` points, but not into spawned tasks
+/// unless they are wrapped in [`propagate`].
+pub async fn scope<F: Future>(context: HashMap<String, ConfidenceValue>, future: F) -> F::Output {
+    let mut merged = current().unwrap_or_default();
+    merged.extend(context);
+    AMBIENT_CONTEXT.scope(Arc::new(merged), future).await
+}
+
+/// Carry the ambient context of the calling task into `future`, e.g. one about to be spawned.
+///
+/// ```ignore
+/// tokio::spawn(ambient_context::propagate(async move { confidence.get_flag("flag", false).await }));
+/// ```
+pub fn propagate<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let context = AMBIENT_CONTEXT.try_with(Arc::clone).ok();
+    async move {
+        match context {
+            Some(context) => AMBIENT_CONTEXT.scope(context, future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// The ambient context of the current task, if it runs inside a [`scope`].
+pub fn current() -> Option<HashMap<String, ConfidenceValue>> {
+    AMBIENT_CONTEXT.try_with(|context| context.as_ref().clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use crate::ambient_context::{current, propagate, scope};
+    use crate::contextual_confidence::Contextual;
+    use crate::models::ResolvedFlags;
+    use crate::resolve::MockNetworkFlagResolver;
+    use crate::{APIConfig, Confidence, ConfidenceValue};
+
+    fn context(key: &str, value: &str) -> HashMap<String, ConfidenceValue> {
+        HashMap::from([(key.to_string(), ConfidenceValue::from(value))])
+    }
+
+    #[tokio::test]
+    async fn scopes_nest_and_end() {
+        assert_eq!(current(), None);
+
+        scope(context("user", "alice"), async {
+            scope(context("session", "s-1"), async {
+                let context = current().unwrap();
+                assert_eq!(context["user"], ConfidenceValue::from("alice"));
+                assert_eq!(context["session"], ConfidenceValue::from("s-1"));
+            })
+            .await;
+            assert!(!current().unwrap().contains_key("session"));
+        })
+        .await;
+
+        assert_eq!(current(), None);
+    }
+
+    #[tokio::test]
+    async fn spawned_tasks_opt_in() {
+        scope(context("user", "alice"), async {
+            let propagated = tokio::spawn(propagate(async { current() })).await.unwrap();
+            let detached = tokio::spawn(async { current() }).await.unwrap();
+
+            assert_eq!(propagated, Some(context("user", "alice")));
+            assert_eq!(detached, None);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn resolves_use_the_ambient_context() {
+        let sent: Arc<Mutex<Vec<HashMap<String, ConfidenceValue>>>> = Arc::default();
+        let recorded = Arc::clone(&sent);
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver.expect_resolve_with_apply().returning(move |_, _, context, _| {
+            recorded.lock().unwrap().push(context.clone());
+            Box::pin(async move { Ok(ResolvedFlags::default()) })
+        });
+        let confidence = Confidence::builder()
+            .api_config(APIConfig::default())
+            .resolver(Arc::new(mock_resolver))
+            .context(context("targeting_key", "default"))
+            .build();
+
+        scope(context("targeting_key", "alice"), confidence.resolve_all()).await.unwrap();
+        confidence.resolve_all().await.unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent[0]["targeting_key"], ConfidenceValue::from("alice"));
+        assert_eq!(sent[1]["targeting_key"], ConfidenceValue::from("default"));
+        assert_eq!(confidence.get_context()["targeting_key"], ConfidenceValue::from("default"));
+    }
+}
@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
-pub use crate::conversion_trait::TypeConversionTrait;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+
+pub use crate::conversion_trait::{ConfidenceFlag, TypeConversionTrait, TypeMismatch};
+use crate::property_path::{self, PathSegment};
 
 /// Hold a value in the evaluation result of supported types.
 #[derive(Clone, PartialEq, Debug)]
@@ -12,6 +15,12 @@ pub enum ConfidenceValue {
   String(String),
   Array(Vec<ConfidenceValue>),
   Struct(StructValue),
+  /// A point in time, sent as an RFC 3339 string such as `2024-05-01T12:30:00Z`.
+  Timestamp(DateTime<Utc>),
+  /// A calendar date, sent as an ISO 8601 string such as `2024-05-01`.
+  Date(NaiveDate),
+  /// A property the resolved variant does not set.
+  Null,
 }
 
 #[derive(Clone, Default, PartialEq, Debug)]
@@ -21,6 +30,11 @@ pub struct StructValue {
 }
 
 impl ConfidenceValue {
+  /// Return `true` if this is a null value.
+  pub fn is_null(&self) -> bool {
+    matches!(self, Self::Null)
+  }
+
   /// Return `true` if this is a bool value.
   pub fn is_bool(&self) -> bool {
     matches!(self, Self::Bool(_))
@@ -39,9 +53,25 @@ impl ConfidenceValue {
     matches!(self, Self::Int(_))
   }
   
-  pub fn as_type<T: TypeConversionTrait>(&self, default_value: &T) -> Option<T> {
-    return Some(default_value.process(&self, default_value));
-  } 
+  /// Try to convert `self` to `T`, returning `None` if it holds a value of another type.
+  pub fn as_type<T: TypeConversionTrait>(&self) -> Option<T> {
+    T::process(self)
+  }
+
+  /// The name of the type of value held, e.g. `"int"` or `"struct"`.
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Self::Bool(_) => "bool",
+      Self::Int(_) => "int",
+      Self::Float(_) => "float",
+      Self::String(_) => "string",
+      Self::Array(_) => "array",
+      Self::Struct(_) => "struct",
+      Self::Timestamp(_) => "timestamp",
+      Self::Date(_) => "date",
+      Self::Null => "null",
+    }
+  }
 
   /// Try to convert `self` to int.
   pub fn as_i64(&self) -> Option<i64> {
@@ -102,6 +132,48 @@ impl ConfidenceValue {
       _ => None,
     }
   }
+
+  /// Look up the value at `path`, e.g. `"checkout.steps[2].title"`.
+  ///
+  /// Keys are separated by `.` and array elements are addressed with `[index]`. Use `\` to escape
+  /// a `.`, `[` or `\` that is part of a key, as in `"version\.2"`. Returns `None` if `path`
+  /// is malformed or nothing is found at it; an empty path returns `self`.
+  pub fn get_path(&self, path: &str) -> Option<&ConfidenceValue> {
+    property_path::parse(path)
+      .ok()?
+      .iter()
+      .try_fold(self, property_path::step)
+  }
+
+  /// Try to convert `self` to a timestamp, parsing RFC 3339 strings.
+  pub fn as_timestamp(&self) -> Option<DateTime<Utc>> {
+    match self {
+      Self::Timestamp(value) => Some(*value),
+      Self::String(value) => DateTime::parse_from_rfc3339(value).ok().map(|value| value.with_timezone(&Utc)),
+      _ => None,
+    }
+  }
+
+  /// Try to convert `self` to a date, parsing `YYYY-MM-DD` strings.
+  pub fn as_date(&self) -> Option<NaiveDate> {
+    match self {
+      Self::Date(value) => Some(*value),
+      Self::String(value) => NaiveDate::parse_from_str(value, DATE_FORMAT).ok(),
+      _ => None,
+    }
+  }
+}
+
+pub(crate) const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The wire format of a timestamp, e.g. `2024-05-01T12:30:00Z`.
+pub(crate) fn format_timestamp(value: &DateTime<Utc>) -> String {
+  value.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// The wire format of a date, e.g. `2024-05-01`.
+pub(crate) fn format_date(value: &NaiveDate) -> String {
+  value.format(DATE_FORMAT).to_string()
 }
 
 impl From<bool> for ConfidenceValue {
@@ -185,6 +257,18 @@ where
   }
 }
 
+impl From<DateTime<Utc>> for ConfidenceValue {
+  fn from(value: DateTime<Utc>) -> Self {
+    Self::Timestamp(value)
+  }
+}
+
+impl From<NaiveDate> for ConfidenceValue {
+  fn from(value: NaiveDate) -> Self {
+    Self::Date(value)
+  }
+}
+
 impl From<StructValue> for ConfidenceValue {
   fn from(value: StructValue) -> Self {
     Self::Struct(value)
@@ -203,10 +287,23 @@ impl StructValue {
   pub fn add_field(&mut self, key: impl Into<String>, value: impl Into<ConfidenceValue>) {
     self.fields.insert(key.into(), value.into());
   }
+
+  /// Look up the value at `path`, e.g. `"steps[2].title"`, with the grammar of
+  /// [`ConfidenceValue::get_path`].
+  pub fn pointer(&self, path: &str) -> Option<&ConfidenceValue> {
+    let segments = property_path::parse(path).ok()?;
+    let (first, rest) = segments.split_first()?;
+    let PathSegment::Key(key) = first else {
+      return None;
+    };
+    rest.iter().try_fold(self.fields.get(key)?, property_path::step)
+  }
 }
 
 #[cfg(test)]
 mod tests {
+  use chrono::TimeZone;
+
   use crate::Confidence;
   use super::*;
 
@@ -249,6 +346,10 @@ mod tests {
       *friends.as_array().unwrap()
     );
 
+    assert_eq!(Some(100i64), id.as_type());
+    assert_eq!(None, id.as_type::<f64>());
+    assert_eq!("int", id.type_name());
+
     let other = alex.fields.get("other").unwrap();
     assert!(other.is_struct());
     assert_eq!(
@@ -263,4 +364,25 @@ mod tests {
         .unwrap()
     );
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn timestamps_and_dates() {
+    use crate::conversion_trait::ToSerdeValueConverter;
+
+    let signed_up = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+    let birthday = NaiveDate::from_ymd_opt(1990, 2, 28).unwrap();
+    let user = StructValue::default()
+      .with_field("signed_up", signed_up)
+      .with_field("birthday", birthday);
+
+    assert_eq!(
+      ConfidenceValue::Struct(user.clone()).convert(),
+      serde_json::json!({ "signed_up": "2024-05-01T12:30:00Z", "birthday": "1990-02-28" })
+    );
+    assert_eq!(Some(signed_up), user.fields["signed_up"].as_type::<DateTime<Utc>>());
+    assert_eq!(Some(birthday), user.fields["birthday"].as_type::<NaiveDate>());
+    assert_eq!(Some(birthday), ConfidenceValue::from("1990-02-28").as_date());
+    assert_eq!(Some(signed_up), ConfidenceValue::from("2024-05-01T14:30:00+02:00").as_timestamp());
+    assert_eq!(None, ConfidenceValue::from("tomorrow").as_timestamp());
+  }
+}
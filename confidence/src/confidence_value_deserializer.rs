@@ -0,0 +1,340 @@
+use std::collections::hash_map;
+use std::fmt;
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::confidence_value::{format_date, format_timestamp, ConfidenceValue, StructValue};
+use crate::evaluation_error::EvaluationErrorCode;
+use crate::property_path::{self, PathSegment};
+
+/// Decode a [`ConfidenceValue`] into any type implementing [`serde::Deserialize`].
+pub fn from_value<T: DeserializeOwned>(value: ConfidenceValue) -> Result<T, DeserializeError> {
+    T::deserialize(value)
+}
+
+/// An error raised while decoding a [`ConfidenceValue`], carrying the path of the offending value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeserializeError {
+    kind: DeserializeErrorKind,
+    path: Vec<PathSegment>,
+    message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DeserializeErrorKind {
+    /// A value was present but of the wrong type or out of range.
+    TypeMismatch,
+    /// The value did not have the expected shape, e.g. a required field was missing.
+    Parse,
+}
+
+impl DeserializeError {
+    /// The path of the value that failed to decode, e.g. `checkout.steps[2].title`.
+    pub fn path(&self) -> String {
+        property_path::format(&self.path)
+    }
+
+    /// The evaluation error code this error is reported as.
+    pub fn error_code(&self) -> EvaluationErrorCode {
+        match self.kind {
+            DeserializeErrorKind::TypeMismatch => EvaluationErrorCode::TypeMismatch,
+            DeserializeErrorKind::Parse => EvaluationErrorCode::ParseError,
+        }
+    }
+
+    fn at(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    fn with_kind(kind: DeserializeErrorKind, message: String) -> Self {
+        DeserializeError {
+            kind,
+            path: Vec::new(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.path(), self.message)
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::with_kind(DeserializeErrorKind::Parse, msg.to_string())
+    }
+
+    fn invalid_type(unexpected: de::Unexpected, expected: &dyn de::Expected) -> Self {
+        Self::with_kind(
+            DeserializeErrorKind::TypeMismatch,
+            format!("invalid type: {unexpected}, expected {expected}"),
+        )
+    }
+
+    fn invalid_value(unexpected: de::Unexpected, expected: &dyn de::Expected) -> Self {
+        Self::with_kind(
+            DeserializeErrorKind::TypeMismatch,
+            format!("invalid value: {unexpected}, expected {expected}"),
+        )
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ConfidenceValue {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            ConfidenceValue::Bool(value) => visitor.visit_bool(value),
+            ConfidenceValue::Int(value) => visitor.visit_i64(value),
+            ConfidenceValue::Float(value) => visitor.visit_f64(value),
+            ConfidenceValue::String(value) => visitor.visit_string(value),
+            ConfidenceValue::Array(values) => visitor.visit_seq(ArrayAccess {
+                values: values.into_iter().enumerate(),
+            }),
+            ConfidenceValue::Struct(value) => value.deserialize_any(visitor),
+            ConfidenceValue::Timestamp(value) => visitor.visit_string(format_timestamp(&value)),
+            ConfidenceValue::Date(value) => visitor.visit_string(format_date(&value)),
+            ConfidenceValue::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            ConfidenceValue::Null => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            ConfidenceValue::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            other => Err(de::Error::invalid_type(unexpected(&other), &"a string enum variant")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> de::Deserializer<'de> for StructValue {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(StructAccess {
+            fields: self.fields.into_iter(),
+            value: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeserializeError> for ConfidenceValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+fn unexpected(value: &ConfidenceValue) -> de::Unexpected<'_> {
+    match value {
+        ConfidenceValue::Bool(value) => de::Unexpected::Bool(*value),
+        ConfidenceValue::Int(value) => de::Unexpected::Signed(*value),
+        ConfidenceValue::Float(value) => de::Unexpected::Float(*value),
+        ConfidenceValue::String(value) => de::Unexpected::Str(value),
+        ConfidenceValue::Array(_) => de::Unexpected::Seq,
+        ConfidenceValue::Struct(_) => de::Unexpected::Map,
+        ConfidenceValue::Timestamp(_) => de::Unexpected::Other("timestamp"),
+        ConfidenceValue::Date(_) => de::Unexpected::Other("date"),
+        ConfidenceValue::Null => de::Unexpected::Unit,
+    }
+}
+
+struct ArrayAccess {
+    values: std::iter::Enumerate<std::vec::IntoIter<ConfidenceValue>>,
+}
+
+impl<'de> SeqAccess<'de> for ArrayAccess {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        match self.values.next() {
+            Some((index, value)) => seed
+                .deserialize(value)
+                .map(Some)
+                .map_err(|err| err.at(PathSegment::Index(index))),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+struct StructAccess {
+    fields: hash_map::IntoIter<String, ConfidenceValue>,
+    value: Option<(String, ConfidenceValue)>,
+}
+
+impl<'de> MapAccess<'de> for StructAccess {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.fields.next() {
+            Some((key, value)) => {
+                self.value = Some((key.clone(), value));
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        match self.value.take() {
+            Some((key, value)) => seed
+                .deserialize(value)
+                .map_err(|err| err.at(PathSegment::Key(key))),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.fields.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::confidence_value::{ConfidenceValue, StructValue};
+    use crate::confidence_value_deserializer::from_value;
+    use crate::evaluation_error::EvaluationErrorCode;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum Layout {
+        Compact,
+        Wide,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Step {
+        title: String,
+        optional: Option<bool>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Checkout {
+        enabled: bool,
+        max_items: u32,
+        ratio: f64,
+        layout: Layout,
+        steps: Vec<Step>,
+    }
+
+    fn checkout() -> ConfidenceValue {
+        ConfidenceValue::Struct(
+            StructValue::default()
+                .with_field("enabled", true)
+                .with_field("max_items", 3)
+                .with_field("ratio", 1)
+                .with_field("layout", "compact")
+                .with_field(
+                    "steps",
+                    ConfidenceValue::Array(vec![
+                        StructValue::default().with_field("title", "cart").into(),
+                        StructValue::default()
+                            .with_field("title", "pay")
+                            .with_field("optional", false)
+                            .into(),
+                    ]),
+                ),
+        )
+    }
+
+    #[test]
+    fn decodes_nested_struct() {
+        let checkout: Checkout = from_value(checkout()).unwrap();
+
+        assert_eq!(
+            checkout,
+            Checkout {
+                enabled: true,
+                max_items: 3,
+                ratio: 1.0,
+                layout: Layout::Compact,
+                steps: vec![
+                    Step { title: "cart".to_string(), optional: None },
+                    Step { title: "pay".to_string(), optional: Some(false) },
+                ],
+            }
+        );
+        assert_ne!(checkout.layout, Layout::Wide);
+    }
+
+    #[test]
+    fn type_errors_carry_the_path() {
+        let mut value = checkout();
+        if let ConfidenceValue::Struct(checkout) = &mut value {
+            checkout.add_field(
+                "steps",
+                ConfidenceValue::Array(vec![StructValue::default().with_field("title", 7).into()]),
+            );
+        }
+
+        let error = from_value::<Checkout>(value).unwrap_err();
+
+        assert_eq!(error.path(), "steps[0].title");
+        assert_eq!(error.error_code(), EvaluationErrorCode::TypeMismatch);
+    }
+
+    #[test]
+    fn missing_fields_are_parse_errors() {
+        let value = ConfidenceValue::Struct(StructValue::default().with_field("enabled", true));
+
+        let error = from_value::<Checkout>(value).unwrap_err();
+
+        assert_eq!(error.error_code(), EvaluationErrorCode::ParseError);
+        assert!(error.to_string().contains("missing field"));
+    }
+}
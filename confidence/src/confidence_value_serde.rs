@@ -0,0 +1,179 @@
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::confidence_value::{format_date, format_timestamp, ConfidenceValue, StructValue, DATE_FORMAT};
+
+/// Values are written in their plain JSON shape: ints and floats stay distinct, structs become
+/// maps and `Null` becomes `null`. Timestamps and dates are written in their wire format, and
+/// strings in exactly that format are read back as timestamps and dates.
+impl Serialize for ConfidenceValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ConfidenceValue::Bool(value) => serializer.serialize_bool(*value),
+            ConfidenceValue::Int(value) => serializer.serialize_i64(*value),
+            ConfidenceValue::Float(value) => serializer.serialize_f64(*value),
+            ConfidenceValue::String(value) => serializer.serialize_str(value),
+            ConfidenceValue::Array(values) => values.serialize(serializer),
+            ConfidenceValue::Struct(value) => value.serialize(serializer),
+            ConfidenceValue::Timestamp(value) => serializer.serialize_str(&format_timestamp(value)),
+            ConfidenceValue::Date(value) => serializer.serialize_str(&format_date(value)),
+            ConfidenceValue::Null => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfidenceValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ConfidenceValueVisitor)
+    }
+}
+
+struct ConfidenceValueVisitor;
+
+impl<'de> Visitor<'de> for ConfidenceValueVisitor {
+    type Value = ConfidenceValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a Confidence value")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(ConfidenceValue::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        Ok(ConfidenceValue::Int(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        match i64::try_from(value) {
+            Ok(value) => Ok(ConfidenceValue::Int(value)),
+            Err(_) => Ok(ConfidenceValue::Float(value as f64)),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        Ok(ConfidenceValue::Float(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(parse_wire_format(value).unwrap_or_else(|| ConfidenceValue::String(value.to_string())))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Self::Value, E> {
+        Ok(parse_wire_format(&value).unwrap_or(ConfidenceValue::String(value)))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(ConfidenceValue::Null)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(ConfidenceValue::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        ConfidenceValue::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(ConfidenceValue::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        StructValue::deserialize(de::value::MapAccessDeserializer::new(map)).map(ConfidenceValue::Struct)
+    }
+}
+
+// The timestamp or date `value` is the wire format of, if any. Only the exact output of
+// `format_timestamp` and `format_date` counts, so other strings come back unchanged.
+fn parse_wire_format(value: &str) -> Option<ConfidenceValue> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, DATE_FORMAT) {
+        return (format_date(&date) == value).then_some(ConfidenceValue::Date(date));
+    }
+    let timestamp = DateTime::parse_from_rfc3339(value).ok()?.with_timezone(&Utc);
+    (format_timestamp(&timestamp) == value).then_some(ConfidenceValue::Timestamp(timestamp))
+}
+
+impl Serialize for StructValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.fields.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StructValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(StructValue {
+            fields: Deserialize::deserialize(deserializer)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use crate::confidence_value::{ConfidenceValue, StructValue};
+
+    fn user() -> StructValue {
+        StructValue::default()
+            .with_field("targeting_key", "user-1")
+            .with_field("visits", 3)
+            .with_field("score", 3.0)
+            .with_field("premium", false)
+            .with_field("tags", vec!["a", "b"])
+            .with_field("device", StructValue::default().with_field("os", "ios"))
+            .with_field("referrer", ConfidenceValue::Null)
+    }
+
+    #[test]
+    fn json_round_trip_keeps_ints_and_floats_apart() {
+        let json = serde_json::to_string(&user()).unwrap();
+        let decoded: StructValue = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded, user());
+        assert_eq!(decoded.fields["visits"], ConfidenceValue::Int(3));
+        assert_eq!(decoded.fields["score"], ConfidenceValue::Float(3.0));
+    }
+
+    #[test]
+    fn values_deserialize_from_plain_json() {
+        let value: ConfidenceValue =
+            serde_json::from_str(r#"{ "limits": [1, 2.5], "label": null, "big": 18446744073709551615 }"#)
+                .unwrap();
+
+        let fields = &value.as_struct().unwrap().fields;
+        assert_eq!(
+            fields["limits"],
+            ConfidenceValue::Array(vec![ConfidenceValue::Int(1), ConfidenceValue::Float(2.5)])
+        );
+        assert!(fields["label"].is_null());
+        assert!(fields["big"].is_f64());
+    }
+
+    #[test]
+    fn dates_and_timestamps_round_trip_in_wire_format() {
+        let date = ConfidenceValue::from(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap());
+        let timestamp = ConfidenceValue::from(Utc.timestamp_millis_opt(1714566600123).unwrap());
+
+        let json = serde_json::to_string(&date).unwrap();
+
+        assert_eq!(json, r#""2024-05-01""#);
+        let decoded: ConfidenceValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, date);
+        let decoded: ConfidenceValue = serde_json::from_str(&serde_json::to_string(&timestamp).unwrap()).unwrap();
+        assert_eq!(decoded, timestamp);
+        for text in ["2024-5-1", "2024-05-01T10:00:00+02:00", "May 1st"] {
+            let decoded: ConfidenceValue = serde_json::from_value(serde_json::Value::from(text)).unwrap();
+            assert_eq!(decoded, ConfidenceValue::from(text), "{text}");
+        }
+    }
+}
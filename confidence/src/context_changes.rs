@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use lru::LruCache;
+use tokio::sync::watch;
+
+use crate::ambient_context;
+use crate::contextual_confidence::{ContextLayer, Contextual};
+use crate::property_path;
+use crate::{Confidence, ConfidenceValue};
+
+// Upper bound on the distinct flag sets remembered for re-fetching; the least recently resolved
+// set is forgotten first.
+const MAX_ACTIVE_FLAG_SETS: usize = 100;
+
+type ContextListener = dyn Fn(&HashMap<String, ConfidenceValue>) + Send + Sync;
+
+/// Whether the flags of a [`Confidence`] have been re-resolved since its context last changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagState {
+    /// No re-fetch is in flight.
+    Ready,
+    /// The context changed and the flags in use are being re-resolved in the background.
+    Reconciling,
+}
+
+/// Identifies a listener registered with [`Confidence::on_context_change`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+/// Listeners and re-fetch bookkeeping shared by a [`Confidence`] and its clones.
+pub(crate) struct ContextChanges {
+    listeners: Mutex<Vec<(ListenerId, Arc<ContextListener>)>>,
+    next_listener_id: AtomicU64,
+    // The children created by `with_context`, held weakly so they are dropped with their last
+    // clone; they see every change of this context and are told about it.
+    children: Mutex<Vec<(Weak<ContextLayer>, Weak<ContextChanges>)>>,
+    // The distinct sets of flag names resolved most recently; an empty set stands for all flags.
+    active_flags: Mutex<LruCache<Vec<String>, ()>>,
+    // Bumped on every change, so only the newest re-fetch reports `Ready`.
+    generation: AtomicU64,
+    state: watch::Sender<FlagState>,
+}
+
+impl Default for ContextChanges {
+    fn default() -> Self {
+        ContextChanges {
+            listeners: Mutex::default(),
+            next_listener_id: AtomicU64::default(),
+            children: Mutex::default(),
+            active_flags: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_ACTIVE_FLAG_SETS).unwrap())),
+            generation: AtomicU64::default(),
+            state: watch::channel(FlagState::Ready).0,
+        }
+    }
+}
+
+impl ContextChanges {
+    /// Remember `flags` as in use, so they are re-fetched after a context change.
+    pub(crate) fn record_resolve(&self, flags: &[String]) {
+        let mut names: Vec<String> = flags
+            .iter()
+            .map(|flag| property_path::whole_flag_key(flag).unwrap_or_else(|| flag.clone()))
+            .collect();
+        names.sort();
+        names.dedup();
+        self.active_flags.lock().unwrap().put(names, ());
+    }
+
+    /// Pass every change of this context on to the child with `context` and `changes`.
+    pub(crate) fn add_child(&self, context: &Arc<ContextLayer>, changes: &Arc<ContextChanges>) {
+        let mut children = self.children.lock().unwrap();
+        children.retain(|(_, changes)| changes.strong_count() > 0);
+        children.push((Arc::downgrade(context), Arc::downgrade(changes)));
+    }
+
+    fn notify(&self, context: &HashMap<String, ConfidenceValue>) {
+        // Listeners run without the lock held, so they may register or remove listeners.
+        let listeners: Vec<_> = self
+            .listeners
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, listener)| Arc::clone(listener))
+            .collect();
+        for listener in listeners {
+            listener(context);
+        }
+    }
+}
+
+impl Confidence {
+    /// Call `listener` with the new context whenever the context of this instance changes.
+    ///
+    /// Changes made through clones count, and so do changes to the parents of a
+    /// [`Contextual::with_context`] child. A child starts without listeners of its own.
+    pub fn on_context_change<F>(&self, listener: F) -> ListenerId
+    where
+        F: Fn(&HashMap<String, ConfidenceValue>) + Send + Sync + 'static,
+    {
+        let id = ListenerId(self.changes.next_listener_id.fetch_add(1, Ordering::Relaxed));
+        self.changes.listeners.lock().unwrap().push((id, Arc::new(listener)));
+        id
+    }
+
+    pub fn remove_context_listener(&self, id: ListenerId) {
+        self.changes.listeners.lock().unwrap().retain(|(listener_id, _)| *listener_id != id);
+    }
+
+    /// Whether a background re-fetch is in flight; always `Ready` unless
+    /// `refetch_on_context_change` is in effect.
+    pub fn flag_state(&self) -> FlagState {
+        *self.changes.state.borrow()
+    }
+
+    /// Wait until the flags in use have been re-resolved with the current context.
+    pub async fn wait_until_ready(&self) {
+        let mut state = self.changes.state.subscribe();
+        // The sender lives as long as `self`, so waiting cannot fail.
+        let _ = state.wait_for(|state| *state == FlagState::Ready).await;
+    }
+
+    /// Notify listeners and, if configured, re-fetch the flags in use in the background; then do
+    /// the same for every child, whose context changed with this one.
+    pub(crate) fn context_changed(&self) {
+        self.changes.notify(&self.get_context());
+        self.refetch_flags_in_use();
+
+        let children: Vec<_> = self
+            .changes
+            .children
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(context, changes)| Some((context.upgrade()?, changes.upgrade()?)))
+            .collect();
+        for (context, changes) in children {
+            // A child differs from its parent only in these two.
+            Confidence { context, changes, ..self.clone() }.context_changed();
+        }
+    }
+
+    fn refetch_flags_in_use(&self) {
+        if !self.refetch {
+            return;
+        }
+        let flag_sets: Vec<Vec<String>> = self
+            .changes
+            .active_flags
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(flags, _)| flags.clone())
+            .collect();
+        if flag_sets.is_empty() {
+            return;
+        }
+        // Without a runtime the flags are resolved again on their next read.
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let generation = self.changes.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.changes.state.send_replace(FlagState::Reconciling);
+
+        let confidence = self.clone();
+        // Resolved with the ambient context of the change, so reads in the same scope hit the cache.
+        handle.spawn(ambient_context::propagate(async move {
+            for flags in flag_sets {
+                // A failed re-fetch is reported by the next read of the flags instead.
+                let _ = confidence.resolve_snapshot(flags).await;
+            }
+            if confidence.changes.generation.load(Ordering::SeqCst) == generation {
+                confidence.changes.state.send_replace(FlagState::Ready);
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use crate::ambient_context::scope;
+    use crate::context_changes::FlagState;
+    use crate::contextual_confidence::Contextual;
+    use crate::models::ResolvedFlags;
+    use crate::resolve::MockNetworkFlagResolver;
+    use crate::context_changes::{ContextChanges, MAX_ACTIVE_FLAG_SETS};
+    use crate::{APIConfig, Confidence, ConfidenceValue, DeferredApply, ResolveCache};
+
+    type SentResolves = Arc<Mutex<Vec<(Vec<String>, HashMap<String, ConfidenceValue>)>>>;
+
+    #[test]
+    fn listeners_see_every_change_until_removed() {
+        let confidence = Confidence::builder().api_config(APIConfig::default()).build();
+        let seen: Arc<Mutex<Vec<HashMap<String, ConfidenceValue>>>> = Arc::default();
+        let recorded = Arc::clone(&seen);
+        let id = confidence.on_context_change(move |context| recorded.lock().unwrap().push(context.clone()));
+
+        confidence.clone().put_context("user_id", ConfidenceValue::from("user-1"));
+        confidence.remove_context_listener(id);
+        confidence.remove_context("user_id");
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0]["user_id"], ConfidenceValue::from("user-1"));
+    }
+
+    #[tokio::test]
+    async fn changes_refetch_the_flags_in_use() {
+        let sent: SentResolves = Arc::default();
+        let recorded = Arc::clone(&sent);
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver.expect_resolve_with_apply().returning(move |_, flags, context, _| {
+            recorded.lock().unwrap().push((flags, context.clone()));
+            Box::pin(async move { Ok(ResolvedFlags::default()) })
+        });
+        let confidence = Confidence::builder()
+            .api_config(APIConfig::default())
+            .resolver(Arc::new(mock_resolver))
+            .cache(ResolveCache::builder().build())
+            .deferred_apply(DeferredApply::builder().build())
+            .refetch_on_context_change(true)
+            .build();
+
+        confidence.resolve_flags(&["checkout.enabled"]).await.unwrap();
+        confidence.put_context("user_id", ConfidenceValue::from("user-1"));
+        assert_eq!(confidence.flag_state(), FlagState::Reconciling);
+        confidence.wait_until_ready().await;
+
+        {
+            let sent = sent.lock().unwrap();
+            assert_eq!(sent.len(), 2);
+            assert_eq!(sent[1].0, vec!["checkout".to_string()]);
+            assert_eq!(sent[1].1["user_id"], ConfidenceValue::from("user-1"));
+        }
+        // Served from the cache filled by the re-fetch.
+        confidence.resolve_flags(&["checkout"]).await.unwrap();
+        assert_eq!(sent.lock().unwrap().len(), 2);
+        assert_eq!(confidence.flag_state(), FlagState::Ready);
+    }
+
+    #[tokio::test]
+    async fn refetch_needs_a_cache_and_deferred_apply() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .times(1)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(ResolvedFlags::default()) }));
+        let confidence = Confidence::builder()
+            .api_config(APIConfig::default())
+            .resolver(Arc::new(mock_resolver))
+            .deferred_apply(DeferredApply::builder().build())
+            .refetch_on_context_change(true)
+            .build();
+
+        confidence.resolve_flags(&["checkout"]).await.unwrap();
+        confidence.put_context("user_id", ConfidenceValue::from("user-1"));
+
+        assert_eq!(confidence.flag_state(), FlagState::Ready);
+    }
+
+    #[test]
+    fn least_recently_resolved_flag_sets_are_forgotten() {
+        let changes = ContextChanges::default();
+
+        changes.record_resolve(&["first".to_string()]);
+        for index in 1..MAX_ACTIVE_FLAG_SETS {
+            changes.record_resolve(&[format!("flag-{index}")]);
+        }
+        changes.record_resolve(&["first.enabled".to_string()]);
+        changes.record_resolve(&["last".to_string()]);
+
+        let active_flags = changes.active_flags.lock().unwrap();
+        assert_eq!(active_flags.len(), MAX_ACTIVE_FLAG_SETS);
+        assert!(active_flags.contains(&vec!["first".to_string()]));
+        assert!(!active_flags.contains(&vec!["flag-1".to_string()]));
+    }
+
+    fn refetching_confidence(sent: &SentResolves) -> Confidence {
+        let recorded = Arc::clone(sent);
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver.expect_resolve_with_apply().returning(move |_, flags, context, _| {
+            recorded.lock().unwrap().push((flags, context.clone()));
+            Box::pin(async move { Ok(ResolvedFlags::default()) })
+        });
+        Confidence::builder()
+            .api_config(APIConfig::default())
+            .resolver(Arc::new(mock_resolver))
+            .cache(ResolveCache::builder().build())
+            .deferred_apply(DeferredApply::builder().build())
+            .refetch_on_context_change(true)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn parent_changes_reach_children() {
+        let sent: SentResolves = Arc::default();
+        let parent = refetching_confidence(&sent);
+        let child = parent.with_context(HashMap::from([("user_id".to_string(), ConfidenceValue::from("user-1"))]));
+        let seen: Arc<Mutex<Vec<HashMap<String, ConfidenceValue>>>> = Arc::default();
+        let recorded = Arc::clone(&seen);
+        child.on_context_change(move |context| recorded.lock().unwrap().push(context.clone()));
+        child.resolve_flags(&["checkout"]).await.unwrap();
+
+        parent.put_context("country", ConfidenceValue::from("SE"));
+        assert_eq!(child.flag_state(), FlagState::Reconciling);
+        child.wait_until_ready().await;
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0]["user_id"], ConfidenceValue::from("user-1"));
+        assert_eq!(seen[0]["country"], ConfidenceValue::from("SE"));
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].1["country"], ConfidenceValue::from("SE"));
+        assert_eq!(sent[1].1["user_id"], ConfidenceValue::from("user-1"));
+    }
+
+    #[tokio::test]
+    async fn refetches_keep_the_ambient_context() {
+        let sent: SentResolves = Arc::default();
+        let confidence = refetching_confidence(&sent);
+        let ambient = HashMap::from([("session".to_string(), ConfidenceValue::from("s-1"))]);
+
+        scope(ambient, async {
+            confidence.resolve_flags(&["checkout"]).await.unwrap();
+            confidence.put_context("user_id", ConfidenceValue::from("user-1"));
+            confidence.wait_until_ready().await;
+            let snapshot = confidence.resolve_flags(&["checkout"]).await.unwrap();
+            assert!(snapshot.is_cached());
+        })
+        .await;
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].1["session"], ConfidenceValue::from("s-1"));
+    }
+}
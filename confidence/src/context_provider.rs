@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::confidence_value::StructValue;
+use crate::{get_sdk_version, ConfidenceValue, SDK_ID};
+
+/// A source of context entries added to every resolve and event.
+///
+/// Provided entries never override keys already present in the context, so values set by the
+/// application always win. Providers registered earlier win over later ones.
+pub trait ContextProvider: Send + Sync {
+    fn provide(&self) -> HashMap<String, ConfidenceValue>;
+}
+
+impl<F> ContextProvider for F
+where
+    F: Fn() -> HashMap<String, ConfidenceValue> + Send + Sync,
+{
+    fn provide(&self) -> HashMap<String, ConfidenceValue> {
+        self()
+    }
+}
+
+/// Adds `sdk`: the id and version of this SDK.
+pub struct SdkProvider;
+
+impl ContextProvider for SdkProvider {
+    fn provide(&self) -> HashMap<String, ConfidenceValue> {
+        let sdk = StructValue::default()
+            .with_field("id", SDK_ID)
+            .with_field("version", get_sdk_version());
+        HashMap::from([("sdk".to_string(), sdk.into())])
+    }
+}
+
+/// Adds `hostname`, read once from the `HOSTNAME` environment variable or `/etc/hostname`.
+pub struct HostnameProvider {
+    hostname: Option<String>,
+}
+
+impl HostnameProvider {
+    pub fn new() -> Self {
+        let hostname = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|hostname| hostname.trim().to_string())
+            .filter(|hostname| !hostname.is_empty());
+        Self { hostname }
+    }
+}
+
+impl Default for HostnameProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContextProvider for HostnameProvider {
+    fn provide(&self) -> HashMap<String, ConfidenceValue> {
+        self.hostname
+            .iter()
+            .map(|hostname| ("hostname".to_string(), hostname.as_str().into()))
+            .collect()
+    }
+}
+
+/// Adds `process_start_time`, the time the current process was started.
+///
+/// The start time is read from `/proc` on Linux; elsewhere the provider adds nothing.
+pub struct ProcessStartProvider {
+    started_at: Option<DateTime<Utc>>,
+}
+
+impl ProcessStartProvider {
+    pub fn new() -> Self {
+        Self { started_at: process_start_time() }
+    }
+}
+
+impl Default for ProcessStartProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContextProvider for ProcessStartProvider {
+    fn provide(&self) -> HashMap<String, ConfidenceValue> {
+        self.started_at
+            .iter()
+            .map(|started_at| ("process_start_time".to_string(), (*started_at).into()))
+            .collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_start_time() -> Option<DateTime<Utc>> {
+    // `/proc` counts in clock ticks; without a tick rate the start time is left out.
+    // SAFETY: `sysconf` only reads a system setting.
+    #[allow(clippy::useless_conversion)] // `c_long` is narrower than `i64` on 32-bit targets
+    let ticks_per_second = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => i64::from(ticks),
+        _ => return None,
+    };
+    // The start time is the 22nd field of `/proc/self/stat`, in ticks since boot. The second
+    // field is the command name in parentheses, which may itself contain spaces.
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    let (_, fields) = stat.rsplit_once(')')?;
+    let ticks_since_boot: i64 = fields.split_whitespace().nth(19)?.parse().ok()?;
+    let boot_time: i64 = std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    let millis_since_boot = ticks_since_boot * 1000 / ticks_per_second;
+    DateTime::from_timestamp_millis(boot_time * 1000 + millis_since_boot)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_start_time() -> Option<DateTime<Utc>> {
+    None
+}
+
+/// Adds `locale` from `LC_ALL` or `LANG`, and `timezone` as the current UTC offset, e.g. `+02:00`.
+pub struct LocaleProvider;
+
+impl ContextProvider for LocaleProvider {
+    fn provide(&self) -> HashMap<String, ConfidenceValue> {
+        let mut context = HashMap::from([(
+            "timezone".to_string(),
+            Local::now().offset().to_string().into(),
+        )]);
+        let locale = ["LC_ALL", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|locale| !locale.is_empty());
+        if let Some(locale) = locale {
+            context.insert("locale".to_string(), locale.into());
+        }
+        context
+    }
+}
+
+/// Adds `app_version`, e.g. `AppVersionProvider::new(env!("CARGO_PKG_VERSION"))`.
+pub struct AppVersionProvider {
+    version: String,
+}
+
+impl AppVersionProvider {
+    pub fn new(version: impl Into<String>) -> Self {
+        Self { version: version.into() }
+    }
+}
+
+impl ContextProvider for AppVersionProvider {
+    fn provide(&self) -> HashMap<String, ConfidenceValue> {
+        HashMap::from([("app_version".to_string(), self.version.clone().into())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use chrono::{Duration, Utc};
+
+    use crate::context_provider::{AppVersionProvider, ContextProvider, ProcessStartProvider, SdkProvider};
+    use crate::models::ResolvedFlags;
+    use crate::resolve::MockNetworkFlagResolver;
+    use crate::{APIConfig, Confidence, ConfidenceValue, SDK_ID};
+
+    #[test]
+    fn built_in_providers() {
+        let sdk = SdkProvider.provide();
+        assert_eq!(sdk["sdk"].get_path("id"), Some(&ConfidenceValue::from(SDK_ID)));
+        assert_eq!(AppVersionProvider::new("1.2.3").provide()["app_version"], ConfidenceValue::from("1.2.3"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn process_start_time_is_read_from_proc() {
+        let provider = ProcessStartProvider::new();
+        let started_at = provider.provide()["process_start_time"].as_timestamp().unwrap();
+
+        assert!(started_at <= Utc::now());
+        assert!(started_at > Utc::now() - Duration::hours(1));
+    }
+
+    #[tokio::test]
+    async fn providers_enrich_the_context_without_overriding_it() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .withf(|_, _, context, _| {
+                context.get("cell") == Some(&ConfidenceValue::from("eu-1"))
+                    && context.get("app_version") == Some(&ConfidenceValue::from("from-app"))
+            })
+            .times(1)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(ResolvedFlags::default()) }));
+
+        let cell = || HashMap::from([("cell".to_string(), ConfidenceValue::from("eu-1"))]);
+        let confidence = Confidence::builder()
+            .api_config(APIConfig::default())
+            .resolver(Arc::new(mock_resolver))
+            .context(HashMap::from([("app_version".to_string(), ConfidenceValue::from("from-app"))]))
+            .context_providers(vec![
+                Arc::new(cell) as Arc<dyn ContextProvider>,
+                Arc::new(AppVersionProvider::new("1.2.3")),
+            ])
+            .build();
+
+        confidence.resolve_all().await.unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use typed_builder::TypedBuilder;
+
+use crate::conversion_trait::ToSerdeValueConverter;
+use crate::evaluation_error::{EvaluationError, EvaluationErrorCode};
+use crate::ConfidenceValue;
+
+/// Checks applied to the evaluation context before it is sent to the resolver.
+///
+/// A context that fails them is rejected locally with `TargetingKeyMissing` or `InvalidContext`
+/// rather than by the resolver.
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct ContextValidation {
+    /// Whether the context must hold a non-empty string under `targeting_key_name`.
+    #[builder(default = true)]
+    pub require_targeting_key: bool,
+    #[builder(default = "targeting_key".to_string(), setter(into))]
+    pub targeting_key_name: String,
+    /// Upper bound on the size of the context once encoded as JSON, in bytes.
+    #[builder(default = 32 * 1024)]
+    pub max_size_bytes: usize,
+    /// Upper bound on how deeply structs and lists may be nested; top-level values are at depth 1.
+    #[builder(default = 8)]
+    pub max_depth: usize,
+}
+
+impl Default for ContextValidation {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl ContextValidation {
+    /// Check `context`, returning the error a resolve with it would be reported with.
+    pub fn validate(&self, context: &HashMap<String, ConfidenceValue>) -> Result<(), EvaluationError> {
+        if self.require_targeting_key {
+            match context.get(&self.targeting_key_name) {
+                Some(ConfidenceValue::String(value)) if !value.is_empty() => {}
+                None | Some(ConfidenceValue::String(_)) | Some(ConfidenceValue::Null) => {
+                    return Err(error(
+                        EvaluationErrorCode::TargetingKeyMissing,
+                        format!("the context has no {}", self.targeting_key_name),
+                    ))
+                }
+                Some(value) => {
+                    return Err(error(
+                        EvaluationErrorCode::InvalidContext,
+                        format!(
+                            "{} must be a string, found {}",
+                            self.targeting_key_name,
+                            value.type_name()
+                        ),
+                    ))
+                }
+            }
+        }
+
+        for (key, value) in context {
+            self.validate_value(key, value, 1)?;
+        }
+
+        let encoded: serde_json::Map<_, _> = context
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone().convert()))
+            .collect();
+        let size = serde_json::Value::Object(encoded).to_string().len();
+        if size > self.max_size_bytes {
+            return Err(error(
+                EvaluationErrorCode::InvalidContext,
+                format!("the context is {size} bytes, more than the limit of {}", self.max_size_bytes),
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_value(&self, path: &str, value: &ConfidenceValue, depth: usize) -> Result<(), EvaluationError> {
+        if depth > self.max_depth {
+            return Err(error(
+                EvaluationErrorCode::InvalidContext,
+                format!("{path} is nested deeper than the limit of {}", self.max_depth),
+            ));
+        }
+        match value {
+            ConfidenceValue::Float(value) if !value.is_finite() => Err(error(
+                EvaluationErrorCode::InvalidContext,
+                format!("{path} is {value}, which cannot be sent"),
+            )),
+            ConfidenceValue::Array(values) => values
+                .iter()
+                .enumerate()
+                .try_for_each(|(index, value)| {
+                    self.validate_value(&format!("{path}[{index}]"), value, depth + 1)
+                }),
+            ConfidenceValue::Struct(value) => value.fields.iter().try_for_each(|(key, value)| {
+                self.validate_value(&format!("{path}.{key}"), value, depth + 1)
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn error(code: EvaluationErrorCode, message: String) -> EvaluationError {
+    EvaluationError::builder()
+        .code(code)
+        .message(format!("Invalid evaluation context: {message}"))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use crate::confidence_value::StructValue;
+    use crate::context_validation::ContextValidation;
+    use crate::evaluation_error::EvaluationErrorCode;
+    use crate::resolve::MockNetworkFlagResolver;
+    use crate::{APIConfig, Confidence, ConfidenceValue};
+
+    fn context(entries: Vec<(&str, ConfidenceValue)>) -> HashMap<String, ConfidenceValue> {
+        entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect()
+    }
+
+    fn code(validation: &ContextValidation, entries: Vec<(&str, ConfidenceValue)>) -> Option<EvaluationErrorCode> {
+        validation.validate(&context(entries)).err().map(|err| err.code)
+    }
+
+    #[test]
+    fn targeting_key_is_required() {
+        let validation = ContextValidation::default();
+
+        assert_eq!(code(&validation, vec![("targeting_key", "user-1".into())]), None);
+        assert_eq!(code(&validation, vec![]), Some(EvaluationErrorCode::TargetingKeyMissing));
+        assert_eq!(
+            code(&validation, vec![("targeting_key", "".into())]),
+            Some(EvaluationErrorCode::TargetingKeyMissing)
+        );
+        assert_eq!(
+            code(&validation, vec![("targeting_key", 7.into())]),
+            Some(EvaluationErrorCode::InvalidContext)
+        );
+
+        let validation = ContextValidation::builder().targeting_key_name("user_id").build();
+        assert_eq!(code(&validation, vec![("user_id", "user-1".into())]), None);
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let validation = ContextValidation::builder()
+            .require_targeting_key(false)
+            .max_size_bytes(64)
+            .max_depth(2)
+            .build();
+        let nested = StructValue::default().with_field("a", StructValue::default().with_field("b", 1));
+
+        assert_eq!(code(&validation, vec![("score", f64::NAN.into())]), Some(EvaluationErrorCode::InvalidContext));
+        assert_eq!(code(&validation, vec![("nested", nested.into())]), Some(EvaluationErrorCode::InvalidContext));
+        assert_eq!(
+            code(&validation, vec![("bio", "x".repeat(64).into())]),
+            Some(EvaluationErrorCode::InvalidContext)
+        );
+        assert_eq!(code(&validation, vec![("tags", vec!["a"].into())]), None);
+    }
+
+    #[tokio::test]
+    async fn invalid_context_is_not_sent() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver.expect_resolve_with_apply().never();
+        let confidence = Confidence::builder()
+            .api_config(APIConfig::default())
+            .resolver(Arc::new(mock_resolver))
+            .context_validation(ContextValidation::default())
+            .build();
+
+        let error = confidence.get_flag("flag.enabled", false).await.unwrap_err();
+
+        assert_eq!(error.code, EvaluationErrorCode::TargetingKeyMissing);
+    }
+}
@@ -1,43 +1,142 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
-use crate::{APIConfig, Confidence, ConfidenceValue};
+use crate::{Confidence, ConfidenceValue};
 
+/// Access to the context of a [`Confidence`].
+///
+/// The context lives behind a lock, so it can be changed through a shared reference, e.g. on a
+/// `Confidence` shared across tasks behind an `Arc`. Clones share one context; use
+/// [`Contextual::with_context`] for a context of its own.
 pub trait Contextual {
-    fn put_context(&mut self, key: &str, value: ConfidenceValue);
+    fn put_context(&self, key: &str, value: ConfidenceValue);
+    /// Remove `key` from the context, masking it if it is inherited from a parent.
+    fn remove_context(&self, key: &str);
+    /// Apply all changes in `update` at once, so no resolve or event sees only some of them.
+    ///
+    /// Like every change, it is reported to [`Confidence::on_context_change`] listeners once.
+    fn update_context(&self, update: ContextUpdate);
+    /// A snapshot of the context, including the context of every parent.
     fn get_context(&self) -> HashMap<String, ConfidenceValue>;
+    /// Create a child that overlays `context` on the context of `self`.
+    ///
+    /// The child shares the resolver, cache and applier of its parent, and only stores the
+    /// keys it sets or removes itself. Later changes to the parent are visible to the child and
+    /// reported to its listeners.
     fn with_context(&self, context: HashMap<String, ConfidenceValue>) -> Confidence;
 }
 
-impl Contextual for Confidence {
-    fn put_context(&mut self, key: &str, value: ConfidenceValue) {
-        self.context.insert(key.to_string(), value);
+/// Context changes applied together by [`Contextual::update_context`].
+///
+/// ```ignore
+/// confidence.update_context(ContextUpdate::new().put("user_id", "user-1").remove("anonymous_id"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ContextUpdate {
+    // `None` removes the key.
+    changes: Vec<(String, Option<ConfidenceValue>)>,
+}
+
+impl ContextUpdate {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn get_context(&self) -> HashMap<String, ConfidenceValue> {
-        let mut context = HashMap::new();
-        for (key, value) in self.context.iter() {
-            context.insert(key.to_string(), value.clone());
+    pub fn put(mut self, key: &str, value: impl Into<ConfidenceValue>) -> Self {
+        self.changes.push((key.to_string(), Some(value.into())));
+        self
+    }
+
+    pub fn remove(mut self, key: &str) -> Self {
+        self.changes.push((key.to_string(), None));
+        self
+    }
+}
+
+/// One scope of context, layered on the context of its parent.
+#[derive(Debug, Default)]
+pub(crate) struct ContextLayer {
+    parent: Option<Arc<ContextLayer>>,
+    // `None` masks a key set by a parent.
+    values: RwLock<HashMap<String, Option<ConfidenceValue>>>,
+}
+
+impl ContextLayer {
+    fn child(parent: Arc<ContextLayer>, context: HashMap<String, ConfidenceValue>) -> Self {
+        ContextLayer {
+            parent: Some(parent),
+            values: RwLock::new(context.into_iter().map(|(key, value)| (key, Some(value))).collect()),
+        }
+    }
+
+    /// The context seen by this layer, with every parent key it does not override or mask.
+    ///
+    /// All layers are read-locked together, root first, so the result is one consistent snapshot.
+    pub(crate) fn merged(&self) -> HashMap<String, ConfidenceValue> {
+        let mut layers = vec![self];
+        while let Some(parent) = &layers[layers.len() - 1].parent {
+            layers.push(parent);
         }
+        let snapshot: Vec<_> = layers.iter().rev().map(|layer| layer.values.read().unwrap()).collect();
 
+        let mut context = HashMap::new();
+        for values in &snapshot {
+            for (key, value) in values.iter() {
+                match value {
+                    Some(value) => context.insert(key.clone(), value.clone()),
+                    None => context.remove(key),
+                };
+            }
+        }
         context
     }
 
+    fn apply(&self, changes: Vec<(String, Option<ConfidenceValue>)>) {
+        let mut values = self.values.write().unwrap();
+        for (key, value) in changes {
+            if value.is_some() || self.parent.is_some() {
+                values.insert(key, value);
+            } else {
+                values.remove(&key);
+            }
+        }
+    }
+}
+
+impl From<HashMap<String, ConfidenceValue>> for ContextLayer {
+    fn from(context: HashMap<String, ConfidenceValue>) -> Self {
+        ContextLayer {
+            parent: None,
+            values: RwLock::new(context.into_iter().map(|(key, value)| (key, Some(value))).collect()),
+        }
+    }
+}
+
+impl Contextual for Confidence {
+    fn put_context(&self, key: &str, value: ConfidenceValue) {
+        self.context.apply(vec![(key.to_string(), Some(value))]);
+        self.context_changed();
+    }
+
+    fn remove_context(&self, key: &str) {
+        self.context.apply(vec![(key.to_string(), None)]);
+        self.context_changed();
+    }
+
+    fn update_context(&self, update: ContextUpdate) {
+        self.context.apply(update.changes);
+        self.context_changed();
+    }
+
+    fn get_context(&self) -> HashMap<String, ConfidenceValue> {
+        self.context.merged()
+    }
+
     fn with_context(&self, context: HashMap<String, ConfidenceValue>) -> Confidence {
-        let api_config = (&self.api_config).clone();
-        let new_context: HashMap<String, ConfidenceValue> = context
-            .iter()
-            .map(|(key, value)| (key.to_string(), value.clone()))
-            .collect();
-
-        Confidence::builder()
-            .api_config(APIConfig {
-                api_key: api_config.api_key,
-                region: api_config.region
-            })
-            .context(new_context)
-            .resolver(Arc::clone(&self.resolver))
-            .build()
+        let context = Arc::new(ContextLayer::child(Arc::clone(&self.context), context));
+        let changes = Arc::default();
+        self.changes.add_child(&context, &changes);
+        Confidence { context, changes, ..self.clone() }
     }
 }
 
@@ -48,28 +147,88 @@ mod tests {
     use std::sync::Arc;
 
     use crate::{APIConfig, Confidence, ConfidenceValue, Region};
-    use crate::contextual_confidence::Contextual;
+    use crate::contextual_confidence::{ContextUpdate, Contextual};
 
     #[test]
     fn test_put_context() {
-        let mut confidence = Confidence::builder()
-            .api_config(APIConfig { api_key: "".to_string(), region: Region::EU })
+        let confidence = Confidence::builder()
+            .api_config(APIConfig { api_key: "".to_string(), region: Region::EU, ..Default::default() })
             .resolver(Arc::new(crate::ConfidenceResolver::default()))
             .build();
         confidence.put_context("key", ConfidenceValue::Int(1));
-        assert_eq!(confidence.context.get("key"), Some(&ConfidenceValue::Int(1)));
+        assert_eq!(confidence.get_context().get("key"), Some(&ConfidenceValue::Int(1)));
     }
 
     #[test]
     fn test_with_context() {
         let confidence = Confidence::builder()
-            .api_config(APIConfig { api_key: "X".to_string(), region: Region::EU })
+            .api_config(APIConfig { api_key: "X".to_string(), region: Region::EU, ..Default::default() })
             .resolver(Arc::new(crate::ConfidenceResolver::default()))
             .build();
 
         let mut context = HashMap::new();
         context.insert("key".to_string(), ConfidenceValue::Int(1));
         let new_confidence = confidence.with_context(context);
-        assert_eq!(new_confidence.context.get("key"), Some(&ConfidenceValue::Int(1)));
+        assert_eq!(new_confidence.get_context().get("key"), Some(&ConfidenceValue::Int(1)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn children_overlay_and_mask_parent_keys() {
+        let request = Confidence::builder()
+            .api_config(APIConfig { api_key: "X".to_string(), region: Region::EU, ..Default::default() })
+            .context(HashMap::from([
+                ("region".to_string(), ConfidenceValue::from("eu")),
+                ("client".to_string(), ConfidenceValue::from("web")),
+            ]))
+            .build();
+
+        let user = request.with_context(HashMap::from([
+            ("targeting_key".to_string(), ConfidenceValue::from("user-1")),
+            ("region".to_string(), ConfidenceValue::from("us")),
+        ]));
+        let session = user.with_context(HashMap::from([("session".to_string(), ConfidenceValue::from("s-1"))]));
+        session.remove_context("client");
+        session.put_context("region", ConfidenceValue::from("ap"));
+
+        assert_eq!(
+            session.get_context(),
+            HashMap::from([
+                ("targeting_key".to_string(), ConfidenceValue::from("user-1")),
+                ("region".to_string(), ConfidenceValue::from("ap")),
+                ("session".to_string(), ConfidenceValue::from("s-1")),
+            ])
+        );
+        assert_eq!(user.get_context()["region"], ConfidenceValue::from("us"));
+        assert_eq!(user.get_context()["client"], ConfidenceValue::from("web"));
+        assert_eq!(request.get_context().len(), 2);
+        assert!(Arc::ptr_eq(&session.resolver, &request.resolver));
+    }
+
+    #[test]
+    fn shared_context_updates_are_atomic_and_live() {
+        let confidence = Arc::new(
+            Confidence::builder()
+                .api_config(APIConfig { api_key: "X".to_string(), region: Region::EU, ..Default::default() })
+                .build(),
+        );
+        let child = confidence.with_context(HashMap::new());
+
+        let writer = {
+            let confidence = Arc::clone(&confidence);
+            std::thread::spawn(move || {
+                for i in 0..1000 {
+                    confidence.update_context(ContextUpdate::new().put("a", i).put("b", i));
+                }
+            })
+        };
+        for _ in 0..1000 {
+            let context = child.get_context();
+            assert_eq!(context.get("a"), context.get("b"));
+        }
+        writer.join().unwrap();
+
+        assert_eq!(child.get_context()["a"], ConfidenceValue::from(999));
+        confidence.remove_context("a");
+        assert!(!child.get_context().contains_key("a"));
+    }
+}
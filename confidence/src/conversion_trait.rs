@@ -1,39 +1,162 @@
+use chrono::{DateTime, NaiveDate, Utc};
 use serde_json::Value;
 
-use crate::confidence_value::{ConfidenceValue, StructValue};
+use crate::confidence_value::{format_date, format_timestamp, ConfidenceValue, StructValue};
+use crate::property_path::{self, PathSegment};
 
-pub trait TypeConversionTrait {
-  fn process(&self, value: &ConfidenceValue, default: &Self) -> Self;
+/// Conversion from a resolved [`ConfidenceValue`] into a concrete Rust type.
+pub trait TypeConversionTrait: Sized {
+  /// The name of the expected value type, used in type mismatch errors.
+  fn type_name() -> String;
+
+  /// Convert `value`, or return `None` if it holds a value of another type.
+  fn process(value: &ConfidenceValue) -> Option<Self>;
+
+  /// Why [`TypeConversionTrait::process`] rejected `value`. Types made of parts, such as lists and
+  /// derived structs, point at the part that does not convert.
+  fn mismatch(value: &ConfidenceValue) -> TypeMismatch {
+    TypeMismatch::new(Self::type_name(), value)
+  }
+}
+
+/// A value, or part of one, that holds another type than expected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeMismatch {
+  /// Where the mismatch is, relative to the converted value, e.g. `banner.opacity`; empty for the
+  /// value itself.
+  pub path: String,
+  pub expected: String,
+  pub found: String,
+}
+
+impl TypeMismatch {
+  pub fn new(expected: String, found: &ConfidenceValue) -> Self {
+    TypeMismatch {
+      path: String::new(),
+      expected,
+      found: found.type_name().to_string(),
+    }
+  }
+
+  /// The same mismatch, seen from the struct holding the value under `key`.
+  pub fn in_field(self, key: &str) -> Self {
+    self.prefixed(PathSegment::Key(key.to_string()))
+  }
+
+  /// The same mismatch, seen from the list holding the value at `index`.
+  pub fn in_element(self, index: usize) -> Self {
+    self.prefixed(PathSegment::Index(index))
+  }
+
+  fn prefixed(self, segment: PathSegment) -> Self {
+    let mut segments = vec![segment];
+    if !self.path.is_empty() {
+      // Paths are always built by `prefixed`, so they parse.
+      segments.extend(property_path::parse(&self.path).unwrap_or_default());
+    }
+    TypeMismatch {
+      path: property_path::format(&segments),
+      ..self
+    }
+  }
+}
+
+/// A flag read as a whole into a Rust type, usually through `#[derive(ConfidenceFlag)]`.
+pub trait ConfidenceFlag: TypeConversionTrait {
+  /// The name of the flag, without the `flags/` prefix.
+  const FLAG_NAME: &'static str;
+
+  /// The value returned when the flag cannot be resolved.
+  fn default_value() -> Self;
 }
 
 impl TypeConversionTrait for i64 {
-  fn process(&self, value: &ConfidenceValue, default: &i64) -> i64 {
-    return value.as_i64().unwrap_or(default.clone());
+  fn type_name() -> String {
+    "int".to_string()
+  }
+
+  fn process(value: &ConfidenceValue) -> Option<i64> {
+    value.as_i64()
   }
 }
 
 impl TypeConversionTrait for bool {
-  fn process(&self, value: &ConfidenceValue, default: &bool) -> bool {
-    return value.as_bool().unwrap_or(default.clone());
+  fn type_name() -> String {
+    "bool".to_string()
+  }
+
+  fn process(value: &ConfidenceValue) -> Option<bool> {
+    value.as_bool()
   }
 }
 
 impl TypeConversionTrait for f64 {
-  fn process(&self, value: &ConfidenceValue, default: &f64) -> f64 {
-    return value.as_f64().unwrap_or(default.clone());
+  fn type_name() -> String {
+    "float".to_string()
+  }
+
+  fn process(value: &ConfidenceValue) -> Option<f64> {
+    value.as_f64()
   }
 }
 
 impl TypeConversionTrait for StructValue {
-  fn process(&self, value: &ConfidenceValue, default: &StructValue) -> StructValue {
-    return value.as_struct().unwrap_or(default).clone()
+  fn type_name() -> String {
+    "struct".to_string()
   }
-}
 
+  fn process(value: &ConfidenceValue) -> Option<StructValue> {
+    value.as_struct().cloned()
+  }
+}
 
 impl TypeConversionTrait for String {
-  fn process(&self, value: &ConfidenceValue, default: &String) -> String {
-    return value.as_str().unwrap_or(default).to_string();
+  fn type_name() -> String {
+    "string".to_string()
+  }
+
+  fn process(value: &ConfidenceValue) -> Option<String> {
+    value.as_str().map(str::to_string)
+  }
+}
+
+impl TypeConversionTrait for DateTime<Utc> {
+  fn type_name() -> String {
+    "timestamp".to_string()
+  }
+
+  fn process(value: &ConfidenceValue) -> Option<DateTime<Utc>> {
+    value.as_timestamp()
+  }
+}
+
+impl TypeConversionTrait for NaiveDate {
+  fn type_name() -> String {
+    "date".to_string()
+  }
+
+  fn process(value: &ConfidenceValue) -> Option<NaiveDate> {
+    value.as_date()
+  }
+}
+
+impl<T: TypeConversionTrait> TypeConversionTrait for Vec<T> {
+  fn type_name() -> String {
+    format!("array<{}>", T::type_name())
+  }
+
+  fn process(value: &ConfidenceValue) -> Option<Vec<T>> {
+    value.as_array()?.iter().map(T::process).collect()
+  }
+
+  fn mismatch(value: &ConfidenceValue) -> TypeMismatch {
+    let element = value
+      .as_array()
+      .and_then(|values| values.iter().enumerate().find(|(_, value)| T::process(value).is_none()));
+    match element {
+      Some((index, value)) => T::mismatch(value).in_element(index),
+      None => TypeMismatch::new(Self::type_name(), value),
+    }
   }
 }
 
@@ -67,6 +190,13 @@ impl ToSerdeValueConverter for ConfidenceValue {
             .collect();
         Value::Object(map)
       }
+      ConfidenceValue::Timestamp(value) => {
+        Value::from(format_timestamp(&value))
+      }
+      ConfidenceValue::Date(value) => {
+        Value::from(format_date(&value))
+      }
+      ConfidenceValue::Null => Value::Null,
     }
   }
 }
\ No newline at end of file
@@ -54,6 +54,22 @@ pub enum EvaluationReason {
   Other(String),
 }
 
+impl EvaluationReason {
+  /// Map a resolve reason reported by the Confidence backend, e.g. `RESOLVE_REASON_MATCH`.
+  ///
+  /// Reasons this SDK does not know about are kept verbatim as [`EvaluationReason::Other`].
+  pub fn from_resolve_reason(reason: &str) -> Self {
+    match reason {
+      "RESOLVE_REASON_MATCH" => Self::TargetingMatch,
+      "RESOLVE_REASON_NO_SEGMENT_MATCH" | "RESOLVE_REASON_NO_TREATMENT_MATCH" => Self::Default,
+      "RESOLVE_REASON_FLAG_ARCHIVED" => Self::Disabled,
+      "RESOLVE_REASON_TARGETING_KEY_ERROR" | "RESOLVE_REASON_ERROR" => Self::Error,
+      "RESOLVE_REASON_UNSPECIFIED" | "" => Self::Unknown,
+      other => Self::Other(other.to_string()),
+    }
+  }
+}
+
 impl ToString for EvaluationReason {
   fn to_string(&self) -> String {
     match self {
@@ -75,6 +91,18 @@ impl ToString for EvaluationReason {
 //  FlagMetadata
 // ============================================================
 
+/// Metadata key holding the token of the resolve that produced the value.
+pub const RESOLVE_TOKEN_METADATA_KEY: &str = "resolve_token";
+
+/// Metadata key holding the full flag name, e.g. `flags/my-flag`.
+pub const FLAG_METADATA_KEY: &str = "flag";
+
+/// Metadata key holding the full variant name, e.g. `flags/my-flag/variants/treatment`.
+pub const VARIANT_METADATA_KEY: &str = "variant";
+
+/// Metadata key holding the resolve reason exactly as reported by the backend.
+pub const RESOLVE_REASON_METADATA_KEY: &str = "resolve_reason";
+
 /// A structure which supports definition of arbitrary properties, with keys of type string, and
 /// values of type boolean, string, or number.
 ///
@@ -186,4 +214,22 @@ impl From<&str> for FlagMetadataValue {
   fn from(value: &str) -> Self {
     Self::String(value.into())
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn resolve_reasons_are_mapped() {
+    assert_eq!(EvaluationReason::from_resolve_reason("RESOLVE_REASON_MATCH"), EvaluationReason::TargetingMatch);
+    assert_eq!(EvaluationReason::from_resolve_reason("RESOLVE_REASON_NO_SEGMENT_MATCH"), EvaluationReason::Default);
+    assert_eq!(EvaluationReason::from_resolve_reason("RESOLVE_REASON_FLAG_ARCHIVED"), EvaluationReason::Disabled);
+    assert_eq!(EvaluationReason::from_resolve_reason("RESOLVE_REASON_TARGETING_KEY_ERROR"), EvaluationReason::Error);
+    assert_eq!(EvaluationReason::from_resolve_reason("RESOLVE_REASON_UNSPECIFIED"), EvaluationReason::Unknown);
+    assert_eq!(
+      EvaluationReason::from_resolve_reason("RESOLVE_REASON_SOMETHING_NEW"),
+      EvaluationReason::Other("RESOLVE_REASON_SOMETHING_NEW".to_string())
+    );
+  }
+}
@@ -0,0 +1,341 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::sync::Notify;
+use typed_builder::TypedBuilder;
+
+use crate::event_sender::{Event, EventRequest};
+use crate::models::{APIConfig, APIURL, SDK};
+use crate::{get_sdk_version, SDK_ID};
+
+/// What [`crate::event_sender::EventSender::track`] does with an event when the queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    /// Discard the new event.
+    #[default]
+    DropNewest,
+    /// Discard the oldest queued event to make room for the new one.
+    DropOldest,
+}
+
+/// Settings for the background publishing of events.
+///
+/// Tracked events are queued and sent in batches to the `events:publish` endpoint, once
+/// `max_batch_size` events are waiting or the oldest has waited for `max_latency`.
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct EventBatching {
+    /// Number of queued events that triggers an immediate send, and the most sent in one request.
+    #[builder(default = 50)]
+    pub max_batch_size: usize,
+    /// How long a queued event may wait before it is sent.
+    #[builder(default = Duration::from_secs(5))]
+    pub max_latency: Duration,
+    /// Number of events that may be queued at once; at least 1, like the buffer of a bounded
+    /// channel, so [`crate::Confidence`] panics on build with a capacity of 0.
+    #[builder(default = 1000)]
+    pub queue_capacity: usize,
+    #[builder(default)]
+    pub queue_full_policy: QueueFullPolicy,
+}
+
+impl Default for EventBatching {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Queues tracked events and sends them in batches.
+#[derive(Clone)]
+pub struct EventPublisher {
+    inner: Arc<PublisherInner>,
+}
+
+struct PublisherInner {
+    config: EventBatching,
+    api_config: APIConfig,
+    http_client: reqwest::Client,
+    state: Mutex<PublisherState>,
+    // Signalled whenever queued events are taken off the queue.
+    space: Notify,
+    // Held while a batch is being sent, so `flush` returns only once earlier sends are done.
+    sending: tokio::sync::Mutex<()>,
+}
+
+#[derive(Default)]
+struct PublisherState {
+    queue: VecDeque<Event>,
+    // Whether a flush after `max_latency` is scheduled.
+    flush_scheduled: bool,
+    // Whether a flush of a full batch is spawned and has not started yet.
+    batch_flush_pending: bool,
+    dropped: u64,
+}
+
+impl fmt::Debug for EventPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventPublisher")
+            .field("config", &self.inner.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EventPublisher {
+    pub fn new(config: EventBatching, api_config: APIConfig, http_client: reqwest::Client) -> Self {
+        // With no room at all, `publish_waiting` would wait forever.
+        assert!(config.queue_capacity > 0, "event queue_capacity must be at least 1");
+        Self {
+            inner: Arc::new(PublisherInner {
+                config,
+                api_config,
+                http_client,
+                state: Mutex::new(PublisherState::default()),
+                space: Notify::new(),
+                sending: tokio::sync::Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Queue `event`, applying the [`QueueFullPolicy`] if the queue is full.
+    pub(crate) fn publish(&self, event: Event) {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.queue.len() >= self.inner.config.queue_capacity {
+            state.dropped += 1;
+            match self.inner.config.queue_full_policy {
+                QueueFullPolicy::DropNewest => return,
+                QueueFullPolicy::DropOldest => {
+                    state.queue.pop_front();
+                }
+            }
+        }
+        state.queue.push_back(event);
+        self.schedule(&mut state);
+    }
+
+    /// Queue `event`, waiting for room if the queue is full.
+    pub(crate) async fn publish_waiting(&self, event: Event) {
+        loop {
+            let space = self.inner.space.notified();
+            {
+                let mut state = self.inner.state.lock().unwrap();
+                if state.queue.len() < self.inner.config.queue_capacity {
+                    state.queue.push_back(event);
+                    self.schedule(&mut state);
+                    return;
+                }
+            }
+            space.await;
+        }
+    }
+
+    /// Number of events discarded so far because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.inner.state.lock().unwrap().dropped
+    }
+
+    fn schedule(&self, state: &mut PublisherState) {
+        // Without a runtime the events stay queued until `flush` is called.
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if state.queue.len() >= self.inner.config.max_batch_size {
+            if state.batch_flush_pending {
+                return;
+            }
+            state.batch_flush_pending = true;
+            let publisher = self.clone();
+            handle.spawn(async move { publisher.flush().await });
+        } else if !state.flush_scheduled {
+            state.flush_scheduled = true;
+            let publisher = self.clone();
+            let max_latency = self.inner.config.max_latency;
+            handle.spawn(async move {
+                tokio::time::sleep(max_latency).await;
+                publisher.flush().await;
+            });
+        }
+    }
+
+    /// Send every queued event now, and wait for sends already in flight.
+    ///
+    /// Events queued while the flush runs are left to a later one, so steady traffic cannot keep
+    /// it from returning.
+    pub async fn flush(&self) {
+        let _sending = self.inner.sending.lock().await;
+        let mut remaining = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.flush_scheduled = false;
+            state.batch_flush_pending = false;
+            state.queue.len()
+        };
+        while remaining > 0 {
+            let batch: Vec<Event> = {
+                let mut state = self.inner.state.lock().unwrap();
+                let size = remaining.min(state.queue.len()).min(self.inner.config.max_batch_size.max(1));
+                remaining -= size;
+                state.queue.drain(..size).collect()
+            };
+            if batch.is_empty() {
+                return;
+            }
+            self.inner.space.notify_waiters();
+            self.send(batch).await;
+        }
+    }
+
+    async fn send(&self, events: Vec<Event>) {
+        let now = Utc::now();
+        let request = EventRequest::builder()
+            .client_secret(self.inner.api_config.api_key.clone())
+            .send_time(now)
+            .events(events)
+            .sdk(SDK::builder().id(SDK_ID).version(get_sdk_version()).build())
+            .build();
+        let body = match serde_json::to_string(&request) {
+            Ok(json) => json,
+            Err(e) => {
+                log::error!("Failed to serialize event request: {e:?}");
+                return;
+            }
+        };
+
+        let url = format!("{}/v1/events:publish", self.inner.api_config.region.events_url());
+        let response = self
+            .inner
+            .http_client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json")
+            .body(body)
+            .send()
+            .await;
+        match response {
+            Ok(response) if !response.status().is_success() => {
+                log::error!("Failed to publish events: status {}", response.status());
+            }
+            Ok(_) => {}
+            Err(err) => log::error!("Failed to publish events: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::event_publisher::{EventBatching, QueueFullPolicy};
+    use crate::event_sender::EventSender;
+    use crate::test_server::TestServer;
+    use crate::{APIConfig, Confidence, Region};
+
+    fn confidence(events_url: &str, event_batching: EventBatching) -> Confidence {
+        Confidence::builder()
+            .api_config(APIConfig {
+                api_key: "secret".to_string(),
+                region: Region::Custom {
+                    resolver_url: "http://localhost:1".to_string(),
+                    events_url: events_url.to_string(),
+                },
+                ..Default::default()
+            })
+            .event_batching(event_batching)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn events_are_sent_in_batches() {
+        let server = TestServer::start(vec![("200 OK", "{}")]).await;
+        let confidence = confidence(
+            &server.url,
+            EventBatching::builder().max_batch_size(2).max_latency(Duration::from_secs(60)).build(),
+        );
+
+        for name in ["first", "second", "third"] {
+            confidence.track(name, HashMap::new());
+        }
+        confidence.flush_events().await;
+
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        assert!(received[0].body.contains("eventDefinitions/first"));
+        assert!(received[0].body.contains("eventDefinitions/second"));
+        assert!(received[1].body.contains("eventDefinitions/third"));
+    }
+
+    #[tokio::test]
+    async fn events_wait_at_most_max_latency() {
+        let server = TestServer::start(vec![("200 OK", "{}")]).await;
+        let confidence = confidence(
+            &server.url,
+            EventBatching::builder().max_latency(Duration::from_millis(50)).build(),
+        );
+
+        confidence.track("navigate", HashMap::new());
+        for _ in 0..100 {
+            if !server.received().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn flush_returns_under_steady_traffic() {
+        let server = TestServer::start(vec![("200 OK", "{}")]).await;
+        let confidence = confidence(
+            &server.url,
+            EventBatching::builder().max_batch_size(2).max_latency(Duration::from_secs(60)).build(),
+        );
+        for name in ["first", "second", "third"] {
+            confidence.track(name, HashMap::new());
+        }
+
+        let producer = confidence.clone();
+        let traffic = tokio::spawn(async move {
+            loop {
+                producer.track("background", HashMap::new());
+                tokio::task::yield_now().await;
+            }
+        });
+        let flushed = tokio::time::timeout(Duration::from_secs(5), confidence.flush_events()).await;
+        traffic.abort();
+
+        assert!(flushed.is_ok());
+        let first_batch = &server.received()[0].body;
+        assert!(first_batch.contains("eventDefinitions/first"));
+    }
+
+    #[test]
+    #[should_panic(expected = "event queue_capacity must be at least 1")]
+    fn empty_queue_is_rejected() {
+        confidence("http://localhost:1", EventBatching::builder().queue_capacity(0).build());
+    }
+
+    #[tokio::test]
+    async fn full_queue_drops_by_policy() {
+        let server = TestServer::start(vec![("200 OK", "{}")]).await;
+        let confidence = confidence(
+            &server.url,
+            EventBatching::builder()
+                .max_latency(Duration::from_secs(60))
+                .queue_capacity(2)
+                .queue_full_policy(QueueFullPolicy::DropOldest)
+                .build(),
+        );
+
+        for name in ["first", "second", "third"] {
+            confidence.track(name, HashMap::new());
+        }
+        confidence.flush_events().await;
+
+        assert_eq!(confidence.dropped_events(), 1);
+        let body = &server.received()[0].body;
+        assert!(!body.contains("eventDefinitions/first"));
+        assert!(body.contains("eventDefinitions/third"));
+    }
+}
@@ -5,8 +5,7 @@ use serde::Serialize;
 use serde_json::Value;
 use typed_builder::TypedBuilder;
 
-use crate::{Confidence, ConfidenceValue, get_sdk_version, SDK_ID};
-use crate::contextual_confidence::Contextual;
+use crate::{Confidence, ConfidenceValue};
 use crate::conversion_trait::ToSerdeValueConverter;
 use crate::models::SDK;
 
@@ -16,68 +15,44 @@ pub trait EventSender {
 
 impl EventSender for Confidence {
     fn track(&self, name: &str, message: HashMap<String, ConfidenceValue>) {
-        let payload = self.get_context();
-
-        let mut context_map: HashMap<String, Value> = payload
-            .iter()
-            .map(|(key, value)| (key.clone(), value.clone().convert()))
-            .collect();
-        let message_map: HashMap<String, Value> = message
-            .iter()
-            .map(|(key, value)| (key.clone(), value.clone().convert()))
-            .collect();
-        for (key, value) in message_map {
-            context_map.insert(key, value);
-        }
-       tokio::spawn(send_event(self.api_config.api_key.clone(), name.to_string(), context_map));
+        self.event_publisher.publish(self.event(name, message));
     }
 }
 
-async fn send_event(client_secret: String, _name: String, _message: HashMap<String, Value>) {
-    let now: DateTime<Utc> = Utc::now();
-    let sdk = SDK::builder().id(SDK_ID).version(get_sdk_version()).build();
-
-    let events = Event::builder()
-        .event_definition(format!("eventDefinitions/{}", _name))
-        .event_time(now)
-        .payload(_message)
-        .build();
+impl Confidence {
+    /// Like [`EventSender::track`], but waits for room in the queue instead of dropping an event
+    /// when it is full.
+    pub async fn track_with_backpressure(&self, name: &str, message: HashMap<String, ConfidenceValue>) {
+        self.event_publisher.publish_waiting(self.event(name, message)).await;
+    }
 
-    let req = &EventRequest::builder()
-        .client_secret(client_secret)
-        .send_time(now)
-        .events(Vec::from([events]))
-        .sdk(sdk)
-        .build();
+    /// Send every queued event, e.g. before shutting down.
+    pub async fn flush_events(&self) {
+        self.event_publisher.flush().await;
+    }
 
-    let body =
-        match serde_json::to_string(req) {
-            Ok(json) => json,
-            Err(e) => {
-                eprintln!("Failed to serialize event request: {:?}", e);
-                return;
-            }
-        };
-    let client = reqwest::Client::new();
-    let response = client
-        .post("https://events.confidence.dev/v1/events:publish")
-        .header("Content-Type", "application/json")
-        .header("Accept", "application/json")
-        .body(body)
-        .send()
-        .await;
-    match response {
-        Ok(success) => {
-            match success.text().await {
-                Ok(_) => {
+    /// Number of events discarded so far because the event queue was full.
+    pub fn dropped_events(&self) -> u64 {
+        self.event_publisher.dropped()
+    }
 
-                }
-                Err(err) => println!("ERROR ->> {}", err)
-            }
+    fn event(&self, name: &str, message: HashMap<String, ConfidenceValue>) -> Event {
+        // The message overrides the context, and the redaction applies to both.
+        let mut payload = self.evaluation_context();
+        payload.extend(message);
+        if let Some(redaction) = &self.event_redaction {
+            payload = redaction.apply(&payload);
         }
-        Err(_) => {
 
-        }
+        let context_map: HashMap<String, Value> = payload
+            .into_iter()
+            .map(|(key, value)| (key, value.convert()))
+            .collect();
+        Event::builder()
+            .event_definition(format!("eventDefinitions/{}", name))
+            .event_time(Utc::now())
+            .payload(context_map)
+            .build()
     }
 }
 
@@ -105,4 +80,33 @@ pub struct Event {
     event_time: DateTime<Utc>,
     #[builder(setter(into))]
     payload: HashMap<String, Value>,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::event_sender::EventSender;
+    use crate::test_server::TestServer;
+    use crate::{APIConfig, Confidence, Region};
+
+    #[tokio::test]
+    async fn events_are_sent_to_custom_url() {
+        let server = TestServer::start(vec![("200 OK", "{}")]).await;
+        let confidence = Confidence::new(APIConfig {
+            api_key: "secret".to_string(),
+            region: Region::Custom {
+                resolver_url: "http://localhost:1".to_string(),
+                events_url: server.url.clone(),
+            },
+            ..Default::default()
+        });
+
+        confidence.track("navigate", HashMap::new());
+        confidence.flush_events().await;
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].path, "/v1/events:publish");
+        assert!(received[0].body.contains("eventDefinitions/navigate"));
+    }
+}
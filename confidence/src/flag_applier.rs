@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use typed_builder::TypedBuilder;
+
+use crate::models::{APIConfig, AppliedFlag};
+use crate::resolve::NetworkFlagResolver;
+
+// Upper bound on the (resolve token, flag) pairs remembered for deduplication.
+const MAX_DEDUPE_ENTRIES: usize = 10_000;
+
+/// Settings for apply-on-access mode.
+///
+/// When configured on [`crate::Confidence`], flags are resolved with `apply: false` and only
+/// reported as applied once their value is read.
+#[derive(Clone, Debug, TypedBuilder)]
+pub struct DeferredApply {
+    /// How long a recorded access may wait before it is sent.
+    #[builder(default = Duration::from_secs(10))]
+    pub flush_interval: Duration,
+    /// Number of pending accesses that triggers an immediate flush.
+    #[builder(default = 100)]
+    pub max_batch_size: usize,
+}
+
+/// Collects flag accesses and sends them in batches to the `flags:apply` endpoint.
+#[derive(Clone)]
+pub struct FlagApplier {
+    inner: Arc<ApplierInner>,
+}
+
+struct ApplierInner {
+    config: DeferredApply,
+    api_config: APIConfig,
+    resolver: Arc<dyn NetworkFlagResolver + Sync + Send>,
+    state: Mutex<ApplierState>,
+}
+
+#[derive(Default)]
+struct ApplierState {
+    pending: Vec<(String, AppliedFlag)>,
+    seen: HashSet<(String, String)>,
+    seen_order: VecDeque<(String, String)>,
+    flush_scheduled: bool,
+    // Whether a flush for a full batch is already spawned.
+    batch_flush_pending: bool,
+}
+
+impl fmt::Debug for FlagApplier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlagApplier")
+            .field("config", &self.inner.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FlagApplier {
+    pub fn new(
+        config: DeferredApply,
+        api_config: APIConfig,
+        resolver: Arc<dyn NetworkFlagResolver + Sync + Send>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(ApplierInner {
+                config,
+                api_config,
+                resolver,
+                state: Mutex::new(ApplierState::default()),
+            }),
+        }
+    }
+
+    /// Record that `flag` from the resolve identified by `resolve_token` was read.
+    ///
+    /// Repeated accesses of the same flag from the same resolve are only sent once.
+    pub fn record(&self, resolve_token: &str, flag: &str) {
+        let key = (resolve_token.to_string(), flag.to_string());
+        let mut state = self.inner.state.lock().unwrap();
+        if !state.seen.insert(key.clone()) {
+            return;
+        }
+        state.seen_order.push_back(key);
+        if state.seen_order.len() > MAX_DEDUPE_ENTRIES {
+            if let Some(oldest) = state.seen_order.pop_front() {
+                state.seen.remove(&oldest);
+            }
+        }
+
+        let applied_flag = AppliedFlag::builder().flag(flag).apply_time(Utc::now()).build();
+        state.pending.push((resolve_token.to_string(), applied_flag));
+
+        // Without a runtime the accesses stay pending until `flush` is called.
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if state.pending.len() >= self.inner.config.max_batch_size {
+            if state.batch_flush_pending {
+                return;
+            }
+            state.batch_flush_pending = true;
+            let applier = self.clone();
+            handle.spawn(async move { applier.flush().await });
+        } else if !state.flush_scheduled {
+            state.flush_scheduled = true;
+            let applier = self.clone();
+            let flush_interval = self.inner.config.flush_interval;
+            handle.spawn(async move {
+                tokio::time::sleep(flush_interval).await;
+                applier.flush().await;
+            });
+        }
+    }
+
+    /// Send every pending access now.
+    pub async fn flush(&self) {
+        let pending = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.flush_scheduled = false;
+            state.batch_flush_pending = false;
+            std::mem::take(&mut state.pending)
+        };
+
+        let mut batches: HashMap<String, Vec<AppliedFlag>> = HashMap::new();
+        for (resolve_token, applied_flag) in pending {
+            batches.entry(resolve_token).or_default().push(applied_flag);
+        }
+
+        for (resolve_token, flags) in batches {
+            self.send(&resolve_token, flags).await;
+        }
+    }
+
+    // Retries happen in the resolver, following `APIConfig::retry_policy`. Accesses that still
+    // fail are forgotten, so the next read of the same flag records them again.
+    async fn send(&self, resolve_token: &str, flags: Vec<AppliedFlag>) {
+        let keys: Vec<(String, String)> = flags
+            .iter()
+            .map(|flag| (resolve_token.to_string(), flag.flag.clone()))
+            .collect();
+        if let Err(err) = self
+            .inner
+            .resolver
+            .apply(&self.inner.api_config, resolve_token, flags)
+            .await
+        {
+            log::error!("Failed to apply flags: {err}");
+            let mut state = self.inner.state.lock().unwrap();
+            for key in &keys {
+                state.seen.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::confidence_value::StructValue;
+    use crate::flag_applier::DeferredApply;
+    use crate::models::{AppliedFlag, ResolveError, ResolvedFlag, ResolvedFlags};
+    use crate::resolve::MockNetworkFlagResolver;
+    use crate::{APIConfig, Confidence, Region};
+
+    type AppliedBatches = Arc<Mutex<Vec<(String, Vec<AppliedFlag>)>>>;
+
+    fn resolved_flags() -> ResolvedFlags {
+        ResolvedFlags {
+            resolve_token: "token".to_string(),
+            flags: vec![ResolvedFlag {
+                flag: "flags/flag".to_string(),
+                variant: "flags/flag/variants/on".to_string(),
+                value: Some(StructValue::default().with_field("enabled", true)),
+                reason: "RESOLVE_REASON_MATCH".to_string(),
+            }],
+        }
+    }
+
+    fn confidence_with(mock_resolver: MockNetworkFlagResolver) -> Confidence {
+        Confidence::builder()
+            .api_config(APIConfig { api_key: "".to_string(), region: Region::Global, ..Default::default() })
+            .resolver(Arc::new(mock_resolver))
+            .deferred_apply(DeferredApply::builder().flush_interval(Duration::from_secs(1)).build())
+            .build()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn resolves_without_apply_and_applies_once_on_access() {
+        let applied: AppliedBatches = Arc::default();
+        let recorded = Arc::clone(&applied);
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .withf(|_, _, _, apply| !apply)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolved_flags()) }));
+        mock_resolver.expect_apply().returning(move |_, token, flags| {
+            recorded.lock().unwrap().push((token.to_string(), flags));
+            Box::pin(async move { Ok(()) })
+        });
+
+        let confidence = confidence_with(mock_resolver);
+        let snapshot = confidence.resolve_all().await.unwrap();
+        assert!(applied.lock().unwrap().is_empty());
+
+        snapshot.get_flag("flag.enabled", false).unwrap();
+        snapshot.get_flag("flag.enabled", false).unwrap();
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        let applied = applied.lock().unwrap();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].0, "token");
+        assert_eq!(applied[0].1.len(), 1);
+        assert_eq!(applied[0].1[0].flag, "flags/flag");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_apply_is_not_retried_on_top_of_the_resolver() {
+        let attempts = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&attempts);
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolved_flags()) }));
+        mock_resolver.expect_apply().returning(move |_, _, _| {
+            let mut attempts = counter.lock().unwrap();
+            *attempts += 1;
+            Box::pin(async move { Err(ResolveError::SerializationError) })
+        });
+
+        let confidence = confidence_with(mock_resolver);
+        confidence.get_flag("flag.enabled", false).await.unwrap();
+        confidence.flush_applies().await;
+
+        assert_eq!(*attempts.lock().unwrap(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_applies_are_recorded_again_on_the_next_read() {
+        let attempts = Arc::new(Mutex::new(0));
+        let applied: AppliedBatches = Arc::default();
+        let (counter, recorded) = (Arc::clone(&attempts), Arc::clone(&applied));
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolved_flags()) }));
+        mock_resolver.expect_apply().returning(move |_, token, flags| {
+            let mut attempts = counter.lock().unwrap();
+            *attempts += 1;
+            if *attempts == 1 {
+                return Box::pin(async move { Err(ResolveError::SerializationError) });
+            }
+            recorded.lock().unwrap().push((token.to_string(), flags));
+            Box::pin(async move { Ok(()) })
+        });
+
+        let confidence = confidence_with(mock_resolver);
+        let snapshot = confidence.resolve_all().await.unwrap();
+        snapshot.get_flag("flag.enabled", false).unwrap();
+        confidence.flush_applies().await;
+        snapshot.get_flag("flag.enabled", false).unwrap();
+        confidence.flush_applies().await;
+
+        let applied = applied.lock().unwrap();
+        assert_eq!(*attempts.lock().unwrap(), 2);
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].1[0].flag, "flags/flag");
+    }
+}
@@ -38,37 +38,8 @@ impl<'de> Visitor<'de> for FlagSchemaVisitor {
                 let entry_map: HashMap<String, Value> = entry_map.into_iter().collect();
 
                 for (key, value) in &entry_map {
-                    if let Some(_) = value.get("boolSchema") {
-                        schema.insert(key.into(), SchemaType::BoolType);
-                        continue;
-                    }
-
-                    if let Some(_) = value.get("intSchema") {
-                        schema.insert(key.into(), SchemaType::IntType);
-                        continue;
-                    }
-
-                    if let Some(_) = value.get("stringSchema") {
-                        schema.insert(key.into(), SchemaType::StringType);
-                        continue;
-                    }
-
-                    if let Some(_) = value.get("doubleSchema") {
-                        schema.insert(key.into(), SchemaType::DoubleType);
-                        continue;
-                    }
-
-                    if let Some(Value::Object(struct_schema)) = value.get("structSchema") {
-                        let struct_schema: HashMap<String, Value> =
-                            struct_schema.clone().into_iter().collect();
-                        let struct_type =
-                            FlagSchema::deserialize(struct_schema.into_deserializer())
-                                .map_err(serde::de::Error::custom)?;
-                        schema.insert(
-                            key.into(),
-                            SchemaType::StructType(Box::new(struct_type.schema)),
-                        );
-                        continue;
+                    if let Some(schema_type) = schema_type(value)? {
+                        schema.insert(key.into(), schema_type);
                     }
                 }
             }
@@ -77,3 +48,40 @@ impl<'de> Visitor<'de> for FlagSchemaVisitor {
         Ok(FlagSchema { schema })
     }
 }
+
+/// Parse a single property schema, e.g. `{"intSchema": {}}`, returning `None` for unknown kinds.
+fn schema_type<E: serde::de::Error>(value: &Value) -> Result<Option<SchemaType>, E> {
+    if value.get("boolSchema").is_some() {
+        return Ok(Some(SchemaType::BoolType));
+    }
+
+    if value.get("intSchema").is_some() {
+        return Ok(Some(SchemaType::IntType));
+    }
+
+    if value.get("stringSchema").is_some() {
+        return Ok(Some(SchemaType::StringType));
+    }
+
+    if value.get("doubleSchema").is_some() {
+        return Ok(Some(SchemaType::DoubleType));
+    }
+
+    if let Some(Value::Object(struct_schema)) = value.get("structSchema") {
+        let struct_schema: HashMap<String, Value> = struct_schema.clone().into_iter().collect();
+        let struct_type = FlagSchema::deserialize(struct_schema.into_deserializer())
+            .map_err(serde::de::Error::custom)?;
+        return Ok(Some(SchemaType::StructType(Box::new(struct_type.schema))));
+    }
+
+    if let Some(list_schema) = value.get("listSchema") {
+        // "listSchema": {"elementSchema": {...}}
+        let element_type = match list_schema.get("elementSchema") {
+            Some(element_schema) => schema_type(element_schema)?,
+            None => None,
+        };
+        return Ok(element_type.map(|element_type| SchemaType::ListType(Box::new(element_type))));
+    }
+
+    Ok(None)
+}
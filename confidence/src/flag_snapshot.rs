@@ -0,0 +1,498 @@
+use serde::de::DeserializeOwned;
+
+use crate::confidence_value_deserializer::from_value;
+use crate::conversion_trait::{ConfidenceFlag, TypeConversionTrait};
+use crate::details::{
+    EvaluationDetails, EvaluationReason, FlagMetadata, FLAG_METADATA_KEY,
+    RESOLVE_REASON_METADATA_KEY, RESOLVE_TOKEN_METADATA_KEY, VARIANT_METADATA_KEY,
+};
+use crate::evaluation_error::{EvaluationError, EvaluationErrorCode};
+use crate::flag_applier::FlagApplier;
+use crate::models::{ResolvedFlag, ResolvedFlags};
+use crate::property_path::{self, PathSegment};
+use crate::ConfidenceValue;
+
+/// The flags resolved by a single call to the resolver.
+///
+/// Reads from a snapshot are served from memory, so any number of `get_flag` calls
+/// only cost the one round trip that produced it.
+#[derive(Debug, Clone, Default)]
+pub struct FlagSnapshot {
+    resolved_flags: ResolvedFlags,
+    cached: bool,
+    applier: Option<FlagApplier>,
+}
+
+impl FlagSnapshot {
+    pub fn new(resolved_flags: ResolvedFlags) -> Self {
+        Self { resolved_flags, cached: false, applier: None }
+    }
+
+    pub(crate) fn cached(resolved_flags: ResolvedFlags) -> Self {
+        Self { resolved_flags, cached: true, applier: None }
+    }
+
+    /// Record every successful read with `applier` instead of relying on the resolver to apply.
+    pub(crate) fn with_applier(mut self, applier: Option<FlagApplier>) -> Self {
+        self.applier = applier;
+        self
+    }
+
+    /// Whether the flags were served from the resolve cache rather than the network.
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+
+    /// The resolve token returned together with the flags of this snapshot.
+    pub fn resolve_token(&self) -> &str {
+        &self.resolved_flags.resolve_token
+    }
+
+    /// Names of the flags in this snapshot, without the `flags/` prefix.
+    pub fn flag_names(&self) -> Vec<&str> {
+        self.resolved_flags
+            .flags
+            .iter()
+            .map(|flag| flag.flag.trim_start_matches("flags/"))
+            .collect()
+    }
+
+    /// Read the value at `flag_key`, or `default_value` with reason
+    /// [`EvaluationReason::Default`] when the resolved variant does not set it.
+    pub fn get_flag<T: TypeConversionTrait>(
+        &self,
+        flag_key: &str,
+        default_value: T,
+    ) -> Result<EvaluationDetails<T>, EvaluationError> {
+        self.evaluate(flag_key, default_value, |value| {
+            value.as_type::<T>().ok_or_else(|| {
+                let mismatch = T::mismatch(value);
+                let location = match mismatch.path.chars().next() {
+                    None => flag_key.to_string(),
+                    Some('[') => format!("{flag_key}{}", mismatch.path),
+                    Some(_) => format!("{flag_key}.{}", mismatch.path),
+                };
+                EvaluationError::builder()
+                    .message(format!(
+                        "schema type is different for {location}: expected {}, found {}",
+                        mismatch.expected, mismatch.found
+                    ))
+                    .code(EvaluationErrorCode::TypeMismatch)
+                    .build()
+            })
+        })
+    }
+
+    /// Read the flag behind `T` as a whole.
+    pub fn get<T: ConfidenceFlag>(&self) -> Result<EvaluationDetails<T>, EvaluationError> {
+        self.get_flag(T::FLAG_NAME, T::default_value())
+    }
+
+    /// Decode the value at `flag_key` into `T` through its [`serde::Deserialize`] implementation.
+    ///
+    /// Both whole flags and any property path can be decoded.
+    pub fn get_flag_as<T: DeserializeOwned>(
+        &self,
+        flag_key: &str,
+        default_value: T,
+    ) -> Result<EvaluationDetails<T>, EvaluationError> {
+        self.evaluate(flag_key, default_value, |value| {
+            from_value(value.clone()).map_err(|err| {
+                EvaluationError::builder()
+                    .message(format!("Failed to decode {flag_key}: {err}"))
+                    .code(err.error_code())
+                    .build()
+            })
+        })
+    }
+
+    fn evaluate<T>(
+        &self,
+        flag_key: &str,
+        default_value: T,
+        convert: impl FnOnce(&ConfidenceValue) -> Result<T, EvaluationError>,
+    ) -> Result<EvaluationDetails<T>, EvaluationError> {
+        let value = self.resolve_value(flag_key)?;
+        let (reason, typed_value) = if value.value.is_null() {
+            (EvaluationReason::Default, default_value)
+        } else if self.cached {
+            (EvaluationReason::Cached, convert(&value.value)?)
+        } else {
+            (value.reason.unwrap_or(EvaluationReason::Default), convert(&value.value)?)
+        };
+
+        if let (Some(applier), Some(flag_name)) = (&self.applier, flag_name(flag_key)) {
+            applier.record(&self.resolved_flags.resolve_token, &flag_name);
+        }
+        let details = EvaluationDetails::builder()
+            .reason(reason)
+            .variant(value.variant.unwrap_or("unknown".to_string()))
+            .value(typed_value)
+            .build();
+        Ok(EvaluationDetails {
+            flag_metadata: value.flag_metadata,
+            ..details
+        })
+    }
+
+    pub(crate) fn resolve_value(
+        &self,
+        flag_key: &str,
+    ) -> Result<EvaluationDetails<ConfidenceValue>, EvaluationError> {
+        let (flag_name, property_path) = match property_path::parse(flag_key) {
+            Ok(segments) => match segments.split_first() {
+                Some((PathSegment::Key(name), property_path)) => {
+                    (format!("flags/{name}"), property_path.to_vec())
+                }
+                _ => return Err(invalid_flag_key("Invalid flag key format".to_string())),
+            },
+            Err(err) => return Err(invalid_flag_key(err.to_string())),
+        };
+
+        match self
+            .resolved_flags
+            .flags
+            .iter()
+            .find(|flag| flag.flag == flag_name)
+        {
+            Some(resolved_flag) => process_flag(
+                resolved_flag,
+                &self.resolved_flags.resolve_token,
+                &property_path,
+            ),
+            None => Err(EvaluationError::builder()
+                .message(format!("Could not find {flag_name} in the resolved flags"))
+                .code(EvaluationErrorCode::FlagNotFound)
+                .build()),
+        }
+    }
+}
+
+fn invalid_flag_key(message: String) -> EvaluationError {
+    EvaluationError::builder()
+        .message(message)
+        .code(EvaluationErrorCode::FlagNotFound)
+        .build()
+}
+
+fn flag_name(flag_key: &str) -> Option<String> {
+    property_path::flag_name(flag_key).map(|name| format!("flags/{name}"))
+}
+
+fn process_flag(
+    resolved_flag: &ResolvedFlag,
+    resolve_token: &str,
+    property_path: &[PathSegment],
+) -> Result<EvaluationDetails<ConfidenceValue>, EvaluationError> {
+    let reason = EvaluationReason::from_resolve_reason(&resolved_flag.reason);
+    let flag_metadata = FlagMetadata::default()
+        .with_value(RESOLVE_TOKEN_METADATA_KEY, resolve_token)
+        .with_value(FLAG_METADATA_KEY, resolved_flag.flag.as_str())
+        .with_value(VARIANT_METADATA_KEY, resolved_flag.variant.as_str())
+        .with_value(RESOLVE_REASON_METADATA_KEY, resolved_flag.reason.as_str());
+    let flag_value = match &resolved_flag.value {
+        Some(value) => ConfidenceValue::Struct(value.clone()),
+        None => ConfidenceValue::Null,
+    };
+    let mut value = &flag_value;
+
+    for segment in property_path {
+        // Every property below an unset one is unset too.
+        if value.is_null() {
+            break;
+        }
+        match property_path::step(value, segment) {
+            Some(next) => value = next,
+            None => {
+                return Err(EvaluationError::builder()
+                    .message(format!(
+                        "No property {} in {}",
+                        property_path::format(property_path),
+                        resolved_flag.flag
+                    ))
+                    .code(EvaluationErrorCode::FlagNotFound)
+                    .build());
+            }
+        }
+    }
+
+    Ok(EvaluationDetails::builder()
+        .reason(reason)
+        .variant(resolved_flag.variant.clone())
+        .flag_metadata(flag_metadata)
+        .value(value.clone())
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::confidence_value::StructValue;
+    use crate::models::{NetworkResolvedFlags, ResolvedFlag, ResolvedFlags};
+    use crate::resolve::MockNetworkFlagResolver;
+    use crate::{APIConfig, Confidence, Region};
+    use crate::evaluation_error::EvaluationErrorCode;
+    use crate::details::{
+        EvaluationReason, FlagMetadataValue, FLAG_METADATA_KEY, RESOLVE_REASON_METADATA_KEY,
+        RESOLVE_TOKEN_METADATA_KEY, VARIANT_METADATA_KEY,
+    };
+
+    fn resolve_response() -> ResolvedFlags {
+        let json_data = r#"
+        {
+          "resolvedFlags": [
+            {
+              "flag": "flags/first",
+              "variant": "flags/first/variants/on",
+              "value": { "enabled": true, "color": "blue", "sizes": [8, 12], "version.2": { "on": true } },
+              "flagSchema": { "schema": {
+                "enabled": { "boolSchema": {} },
+                "color": { "stringSchema": {} },
+                "sizes": { "listSchema": { "elementSchema": { "intSchema": {} } } },
+                "version.2": { "structSchema": { "schema": { "on": { "boolSchema": {} } } } }
+              } },
+              "reason": "RESOLVE_REASON_MATCH"
+            },
+            {
+              "flag": "flags/second",
+              "variant": "flags/second/variants/control",
+              "value": { "limit": 12, "label": null },
+              "flagSchema": { "schema": {
+                "limit": { "intSchema": {} },
+                "label": { "stringSchema": {} }
+              } },
+              "reason": "RESOLVE_REASON_MATCH"
+            }
+          ],
+          "resolveToken": "token"
+        }"#;
+        let network_flags: NetworkResolvedFlags = serde_json::from_str(json_data).unwrap();
+        network_flags.try_into().unwrap()
+    }
+
+    fn confidence_with(mock_resolver: MockNetworkFlagResolver) -> Confidence {
+        Confidence::builder()
+            .api_config(APIConfig { api_key: "".to_string(), region: Region::Global, ..Default::default() })
+            .resolver(Arc::new(mock_resolver))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn resolve_flags_uses_one_request() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .withf(|_, flags, _, _| flags == &vec!["first".to_string(), "second".to_string()])
+            .times(1)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolve_response()) }));
+
+        let snapshot = confidence_with(mock_resolver)
+            .resolve_flags(&["second", "first.enabled", "first.color", "second"])
+            .await
+            .unwrap();
+
+        assert!(snapshot.get_flag("first.enabled", false).unwrap().value);
+        assert_eq!(snapshot.get_flag("first.color", "".to_string()).unwrap().value, "blue");
+        assert_eq!(snapshot.get_flag("second.limit", 0i64).unwrap().value, 12);
+        assert_eq!(snapshot.resolve_token(), "token");
+    }
+
+    #[tokio::test]
+    async fn resolve_all_requests_every_flag() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .withf(|_, flags, _, _| flags.is_empty())
+            .times(1)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolve_response()) }));
+
+        let snapshot = confidence_with(mock_resolver).resolve_all().await.unwrap();
+
+        assert_eq!(snapshot.flag_names(), vec!["first", "second"]);
+    }
+
+    #[tokio::test]
+    async fn details_carry_resolve_metadata() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolve_response()) }));
+
+        let snapshot = confidence_with(mock_resolver).resolve_all().await.unwrap();
+        let details = snapshot.get_flag("second.limit", 0i64).unwrap();
+        let metadata = details.flag_metadata.unwrap();
+
+        assert_eq!(details.reason, Some(EvaluationReason::TargetingMatch));
+        assert_eq!(metadata.values[RESOLVE_TOKEN_METADATA_KEY], FlagMetadataValue::from("token"));
+        assert_eq!(metadata.values[FLAG_METADATA_KEY], FlagMetadataValue::from("flags/second"));
+        assert_eq!(
+            metadata.values[VARIANT_METADATA_KEY],
+            FlagMetadataValue::from("flags/second/variants/control")
+        );
+        assert_eq!(
+            metadata.values[RESOLVE_REASON_METADATA_KEY],
+            FlagMetadataValue::from("RESOLVE_REASON_MATCH")
+        );
+    }
+
+    #[tokio::test]
+    async fn type_mismatch_is_reported() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolve_response()) }));
+
+        let snapshot = confidence_with(mock_resolver).resolve_all().await.unwrap();
+        let error = snapshot.get_flag("first.color", 0i64).unwrap_err();
+
+        assert_eq!(error.code, EvaluationErrorCode::TypeMismatch);
+        assert_eq!(
+            error.message.unwrap(),
+            "schema type is different for first.color: expected int, found string"
+        );
+        let error = snapshot.get_flag("first", false).unwrap_err();
+        assert_eq!(error.code, EvaluationErrorCode::TypeMismatch);
+    }
+
+    #[tokio::test]
+    async fn flags_decode_into_structs() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct First {
+            enabled: bool,
+            color: String,
+        }
+
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolve_response()) }));
+
+        let snapshot = confidence_with(mock_resolver).resolve_all().await.unwrap();
+        let default = First { enabled: false, color: "red".to_string() };
+        let details = snapshot.get_flag_as("first", default).unwrap();
+
+        assert_eq!(details.value, First { enabled: true, color: "blue".to_string() });
+        assert_eq!(snapshot.get_flag_as("second.limit", 0u8).unwrap().value, 12);
+        let error = snapshot.get_flag_as("first.color", 0u8).unwrap_err();
+        assert_eq!(error.code, EvaluationErrorCode::TypeMismatch);
+    }
+
+    #[tokio::test]
+    async fn missing_flag_in_snapshot() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolve_response()) }));
+
+        let snapshot = confidence_with(mock_resolver).resolve_all().await.unwrap();
+        let error = snapshot.get_flag("third.enabled", false).unwrap_err();
+
+        assert_eq!(error.code, EvaluationErrorCode::FlagNotFound);
+    }
+
+    #[tokio::test]
+    async fn unset_properties_return_the_default() {
+        #[derive(serde::Deserialize)]
+        struct Second {
+            label: Option<String>,
+        }
+
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolve_response()) }));
+
+        let snapshot = confidence_with(mock_resolver).resolve_all().await.unwrap();
+        let details = snapshot.get_flag("second.label", "fallback".to_string()).unwrap();
+
+        assert_eq!(details.value, "fallback");
+        assert_eq!(details.reason, Some(EvaluationReason::Default));
+        assert_eq!(details.variant, Some("flags/second/variants/control".to_string()));
+
+        let default = Second { label: Some("fallback".to_string()) };
+        assert_eq!(snapshot.get_flag_as("second", default).unwrap().value.label, None);
+    }
+
+    #[tokio::test]
+    async fn flags_without_a_matching_segment_return_the_default() {
+        let json_data = r#"
+        {
+          "resolvedFlags": [
+            {
+              "flag": "flags/third",
+              "variant": "",
+              "value": null,
+              "flagSchema": null,
+              "reason": "RESOLVE_REASON_NO_SEGMENT_MATCH"
+            }
+          ],
+          "resolveToken": "token"
+        }"#;
+        let network_flags: NetworkResolvedFlags = serde_json::from_str(json_data).unwrap();
+        let resolved_flags: ResolvedFlags = network_flags.try_into().unwrap();
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .returning(move |_, _, _, _| {
+                let resolved_flags = resolved_flags.clone();
+                Box::pin(async move { Ok(resolved_flags) })
+            });
+
+        let snapshot = confidence_with(mock_resolver).resolve_all().await.unwrap();
+        let property = snapshot.get_flag("third.enabled", true).unwrap();
+        let whole = snapshot.get_flag_as("third", 7i64).unwrap();
+
+        assert!(property.value);
+        assert_eq!(property.reason, Some(EvaluationReason::Default));
+        assert_eq!(whole.value, 7);
+        assert_eq!(whole.reason, Some(EvaluationReason::Default));
+    }
+
+    #[tokio::test]
+    async fn flag_names_may_contain_escaped_dots() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .withf(|_, flags, _, _| flags == &vec![r"release\.2".to_string()])
+            .returning(|_, _, _, _| {
+                Box::pin(async move {
+                    Ok(ResolvedFlags {
+                        resolve_token: "token".to_string(),
+                        flags: vec![ResolvedFlag {
+                            flag: "flags/release.2".to_string(),
+                            variant: "flags/release.2/variants/on".to_string(),
+                            value: Some(StructValue::default().with_field("enabled", true)),
+                            reason: "RESOLVE_REASON_MATCH".to_string(),
+                        }],
+                    })
+                })
+            });
+        let confidence = confidence_with(mock_resolver);
+
+        assert!(confidence.get_flag(r"release\.2.enabled", false).await.unwrap().value);
+        let error = confidence.get_flag("release..2", false).await.unwrap_err();
+        assert_eq!(error.code, EvaluationErrorCode::FlagNotFound);
+    }
+
+    #[tokio::test]
+    async fn flag_keys_address_list_elements_and_escaped_keys() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .withf(|_, flags, _, _| flags == &vec!["first".to_string()])
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolve_response()) }));
+        let confidence = confidence_with(mock_resolver);
+
+        let details = confidence.get_flag("first.sizes[1]", 0i64).await.unwrap();
+        assert_eq!(details.value, 12);
+
+        let snapshot = confidence.resolve_flags(&["first.sizes[1]"]).await.unwrap();
+        assert!(snapshot.get_flag(r"first.version\.2.on", false).unwrap().value);
+        assert!(snapshot.get_flag("second.label.text", "fallback".to_string()).is_ok());
+        for missing in ["first.sizes[2]", "first.enabled.nested", "first..color"] {
+            let error = snapshot.get_flag(missing, 0i64).unwrap_err();
+            assert_eq!(error.code, EvaluationErrorCode::FlagNotFound, "{missing}");
+        }
+    }
+}
@@ -1,33 +1,60 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use serde::de::DeserializeOwned;
 use typed_builder::TypedBuilder;
 
 use conversion_trait::TypeConversionTrait;
 use details::EvaluationDetails;
-use evaluation_error::EvaluationError;
+use evaluation_error::{EvaluationError, EvaluationErrorCode};
 
+pub use crate::ambient_context::scope;
 pub use crate::confidence_value::ConfidenceValue;
-use crate::confidence_value::StructValue;
-use crate::details::EvaluationReason;
-use crate::evaluation_error::EvaluationErrorCode;
+pub use crate::context_provider::ContextProvider;
+pub use crate::context_validation::ContextValidation;
+pub use crate::conversion_trait::ConfidenceFlag;
+#[cfg(feature = "derive")]
+pub use spotify_confidence_sdk_derive::ConfidenceFlag;
+pub use crate::flag_applier::DeferredApply;
+use crate::context_changes::ContextChanges;
+use crate::event_publisher::EventPublisher;
+pub use crate::event_publisher::{EventBatching, QueueFullPolicy};
+use crate::contextual_confidence::ContextLayer;
+use crate::flag_applier::FlagApplier;
+pub use crate::flag_snapshot::FlagSnapshot;
 pub use crate::models::APIConfig;
+pub use crate::models::ExtraFieldPolicy;
+pub use crate::models::HttpConfig;
 pub use crate::models::Region;
-use crate::models::ResolvedFlag;
+pub use crate::models::RetryPolicy;
 use crate::models::ResolvedFlags;
 use crate::models::ResolveError;
+use crate::redaction::RedactionRules;
 pub use crate::resolve::ConfidenceResolver;
+pub use crate::resolve_cache::ResolveCache;
 use crate::resolve::NetworkFlagResolver;
 
 mod flag_schema_deserializer;
+pub mod flag_applier;
+pub mod flag_snapshot;
 pub mod models;
 pub mod resolve;
+pub mod resolve_cache;
 pub mod confidence_value;
+pub mod confidence_value_deserializer;
+mod confidence_value_serde;
+mod property_path;
 pub mod evaluation_error;
 pub mod details;
 mod conversion_trait;
 pub mod contextual_confidence;
+pub mod context_validation;
+pub mod context_provider;
+pub mod context_changes;
+pub mod redaction;
+pub mod ambient_context;
 pub mod event_sender;
+pub mod event_publisher;
 
 pub static SDK_ID: &str = "SDK_ID_RUST_CONFIDENCE";
 
@@ -36,151 +63,238 @@ pub fn get_sdk_version() -> String {
     return version.to_string();
 }
 
-#[derive(TypedBuilder)]
+#[derive(TypedBuilder, Clone)]
 pub struct Confidence {
     #[builder(setter(into))]
     api_config: APIConfig,
-    #[builder(default, setter(into))]
-    context: HashMap<String, ConfidenceValue>,
-    resolver: Arc<dyn NetworkFlagResolver + Sync + Send>
+    #[builder(default, setter(transform = |context: HashMap<String, ConfidenceValue>| Arc::new(ContextLayer::from(context))))]
+    context: Arc<ContextLayer>,
+    /// Pool, keep-alive and HTTP/2 settings of the client built when no `http_client` is given.
+    #[builder(default)]
+    #[allow(dead_code)] // only read by the default of `http_client`
+    http_config: HttpConfig,
+    /// The client used for all HTTP traffic, e.g. one with custom TLS roots or a proxy; built from
+    /// `http_config` by default.
+    #[builder(default = api_config.build_http_client(&http_config))]
+    #[allow(dead_code)] // only read by the defaults of the fields below
+    http_client: reqwest::Client,
+    #[builder(default = Arc::new(ConfidenceResolver::new(http_client.clone())))]
+    resolver: Arc<dyn NetworkFlagResolver + Sync + Send>,
+    #[builder(default, setter(transform = |cache: ResolveCache| Some(Arc::new(cache))))]
+    cache: Option<Arc<ResolveCache>>,
+    #[builder(default, setter(strip_option))]
+    deferred_apply: Option<DeferredApply>,
+    /// Sources of context entries added to every resolve and event, e.g. the app version.
+    #[builder(default)]
+    context_providers: Vec<Arc<dyn ContextProvider>>,
+    /// Checks the evaluation context must pass before it is sent to the resolver.
+    #[builder(default, setter(strip_option))]
+    context_validation: Option<ContextValidation>,
+    /// Redaction applied to the context sent with resolves, after validation.
+    #[builder(default, setter(strip_option))]
+    resolve_redaction: Option<RedactionRules>,
+    /// Redaction applied to the context sent with events.
+    #[builder(default, setter(strip_option))]
+    event_redaction: Option<RedactionRules>,
+    /// How tracked events are queued and batched.
+    #[builder(default)]
+    #[allow(dead_code)] // only read by the default of `event_publisher`
+    event_batching: EventBatching,
+    #[builder(
+        setter(skip),
+        default = EventPublisher::new(event_batching.clone(), api_config.clone(), http_client.clone())
+    )]
+    event_publisher: EventPublisher,
+    /// Whether a context change re-resolves the flags in use in the background, to warm the
+    /// resolve cache; see [`Confidence::wait_until_ready`]. Ignored unless `cache` and
+    /// `deferred_apply` are set too, as the results would be thrown away or applied unread.
+    #[builder(default)]
+    #[allow(dead_code)] // only read by the default of `refetch`
+    refetch_on_context_change: bool,
+    #[builder(setter(skip), default = refetch_on_context_change && cache.is_some() && deferred_apply.is_some())]
+    refetch: bool,
+    #[builder(setter(skip), default)]
+    changes: Arc<ContextChanges>,
+    #[builder(
+        setter(skip),
+        default = deferred_apply.clone().map(|config| FlagApplier::new(config, api_config.clone(), Arc::clone(&resolver)))
+    )]
+    applier: Option<FlagApplier>,
 }
 
 impl Confidence {
+    /// A client with default settings and an empty context; see [`Confidence::builder`] for the rest.
     pub fn new(api_config: APIConfig) -> Self {
-        let mut map = HashMap::new();
-        map.insert("targeting_key".to_string(), ConfidenceValue::String("Sample".to_string()));
+        let http_client = api_config.build_http_client(&HttpConfig::default());
+        let event_publisher = EventPublisher::new(EventBatching::default(), api_config.clone(), http_client.clone());
         Self {
             api_config,
-            context: map,
-            resolver: Arc::new(ConfidenceResolver::default())
+            context: Arc::default(),
+            resolver: Arc::new(ConfidenceResolver::new(http_client.clone())),
+            http_client,
+            http_config: HttpConfig::default(),
+            cache: None,
+            deferred_apply: None,
+            context_providers: Vec::new(),
+            context_validation: None,
+            resolve_redaction: None,
+            event_redaction: None,
+            event_batching: EventBatching::default(),
+            event_publisher,
+            refetch_on_context_change: false,
+            refetch: false,
+            changes: Arc::default(),
+            applier: None,
         }
     }
 
+    /// The context sent with resolves and events: the own context of this instance and its
+    /// parents, overlaid with the ambient context and enriched by the context providers.
+    pub(crate) fn evaluation_context(&self) -> HashMap<String, ConfidenceValue> {
+        self.enrich(self.context.merged())
+    }
+
+    fn enrich(&self, mut context: HashMap<String, ConfidenceValue>) -> HashMap<String, ConfidenceValue> {
+        if let Some(ambient) = ambient_context::current() {
+            context.extend(ambient);
+        }
+        for provider in &self.context_providers {
+            for (key, value) in provider.provide() {
+                context.entry(key).or_insert(value);
+            }
+        }
+        context
+    }
+
     async fn fetch_resolved_flags(
         &self,
-        _flag_key: &str,
+        flags: Vec<String>,
         evaluation_context: &HashMap<String, ConfidenceValue>,
     ) -> Result<ResolvedFlags, ResolveError> {
+        // In apply-on-access mode the flags are applied by the applier once they are read.
+        let apply = self.deferred_apply.is_none();
         self.resolver
-            .resolve(
-                &self.api_config,
-                [_flag_key.to_string()].into(),
-                evaluation_context,
-            )
+            .resolve_with_apply(&self.api_config, flags, evaluation_context, apply)
             .await
     }
-    async fn resolve_value(
-        &self,
-        _flag_key: &str,
-        evaluation_context: &HashMap<String, ConfidenceValue>,
-    ) -> Result<EvaluationDetails<ConfidenceValue>, EvaluationError> {
-        let resolved_flags_result = self
-            .fetch_resolved_flags(_flag_key, evaluation_context)
-            .await;
-
-        let resolved_flags = match resolved_flags_result {
-            Ok(result) => result.flags,
-            Err(e) => {
-                return Err(EvaluationError::builder()
-                    .message(&format!("Failed to fetch resolved flags: {:?}", e))
-                    .code(EvaluationErrorCode::FlagNotFound)
-                    .build());
-            }
-        };
-
-        let flag_segments: Vec<&str> = _flag_key.split(".").collect();
-        let flag_name = match flag_segments.first() {
-            Some(name) => format!("flags/{}", name),
-            None => {
-                return Err(EvaluationError::builder()
-                    .message("Invalid flag key format")
-                    .code(EvaluationErrorCode::FlagNotFound)
-                    .build());
-            }
-        };
-        let property_path = flag_segments[1..].to_vec();
-
-        if resolved_flags.len() == 0 {
-            Err(EvaluationError::builder()
-                .message("Could not fetch the flag")
-                .code(EvaluationErrorCode::FlagNotFound)
-                .build())
-        } else {
-            if resolved_flags[0].flag == flag_name {
-                // todo - if property path is empty
-                self.process_flag(&resolved_flags[0], property_path)
-            } else {
-                Err(EvaluationError::builder()
-                    .message("The fetched flag name doesn't match")
+
+    /// Resolve the given flags in a single request to the resolver.
+    ///
+    /// Flag keys may carry a property path (`"my-flag.property"`); only the flag names are
+    /// sent to the resolver, each once, and the returned snapshot serves reads of any property.
+    pub async fn resolve_flags(&self, flag_keys: &[&str]) -> Result<FlagSnapshot, EvaluationError> {
+        let mut flags = Vec::with_capacity(flag_keys.len());
+        for key in flag_keys {
+            let flag = property_path::whole_flag_key(key).ok_or_else(|| {
+                EvaluationError::builder()
+                    .message(format!("Invalid flag key {key:?}"))
                     .code(EvaluationErrorCode::FlagNotFound)
-                    .build())
-            }
+                    .build()
+            })?;
+            flags.push(flag);
         }
+        flags.sort();
+        flags.dedup();
+        self.resolve_snapshot(flags).await
     }
 
-    fn process_flag(
-        &self,
-        resolved_flag: &ResolvedFlag,
-        property_path: Vec<&str>,
-    ) -> Result<EvaluationDetails<ConfidenceValue>, EvaluationError> {
-        let mut last_struct: &StructValue = &resolved_flag.value;
-
-        for path in property_path {
-            if let Some(value) = last_struct.fields.get(path) {
-                if let ConfidenceValue::Struct(struct_value) = value {
-                    last_struct = struct_value;
-                    continue;
-                } else {
-                    return Ok(EvaluationDetails::builder()
-                        .reason(EvaluationReason::TargetingMatch)
-                        .variant(resolved_flag.variant.clone())
-                        .value(value.clone())
-                        .build());
-                }
-            } else {
-                return Err(EvaluationError::builder()
-                    .message("The fetched flag name doesn't match")
-                    .code(EvaluationErrorCode::FlagNotFound)
-                    .build());
-            }
+    /// Resolve every flag enabled for the client in a single request to the resolver.
+    pub async fn resolve_all(&self) -> Result<FlagSnapshot, EvaluationError> {
+        self.resolve_snapshot(Vec::new()).await
+    }
+
+    /// Drop every entry from the resolve cache, if one is configured.
+    pub fn invalidate_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_all();
         }
+    }
 
-        let value = ConfidenceValue::Struct(last_struct.clone());
+    /// Drop the entries the resolve cache holds for `context`, as added to the context of this
+    /// instance, e.g. by [`contextual_confidence::Contextual::with_context`]. It is completed and
+    /// redacted the same way as for a resolve, so it matches the entries those resolves left.
+    pub fn invalidate_cache_for(&self, context: HashMap<String, ConfidenceValue>) {
+        if let Some(cache) = &self.cache {
+            let mut merged = self.context.merged();
+            merged.extend(context);
+            cache.invalidate(&self.redact_for_resolve(self.enrich(merged)));
+        }
+    }
+
+    fn redact_for_resolve(&self, context: HashMap<String, ConfidenceValue>) -> HashMap<String, ConfidenceValue> {
+        match &self.resolve_redaction {
+            Some(redaction) => redaction.apply(&context),
+            None => context,
+        }
+    }
+
+    /// Send every flag access still waiting to be applied, e.g. before shutting down.
+    ///
+    /// Does nothing unless deferred apply is configured.
+    pub async fn flush_applies(&self) {
+        if let Some(applier) = &self.applier {
+            applier.flush().await;
+        }
+    }
+
+    async fn resolve_snapshot(&self, flags: Vec<String>) -> Result<FlagSnapshot, EvaluationError> {
+        let context = self.evaluation_context();
+        if let Some(validation) = &self.context_validation {
+            validation.validate(&context)?;
+        }
+        let context = self.redact_for_resolve(context);
+        if self.refetch {
+            self.changes.record_resolve(&flags);
+        }
+        let cache_key = self.cache.as_ref().map(|cache| cache.key(&flags, &context));
+        if let (Some(cache), Some(key)) = (&self.cache, &cache_key) {
+            if let Some(resolved_flags) = cache.get(key) {
+                return Ok(FlagSnapshot::cached(resolved_flags).with_applier(self.applier.clone()));
+            }
+        }
 
-        return Ok(EvaluationDetails::builder()
-            .reason(EvaluationReason::Default)
-            .variant(resolved_flag.variant.clone())
-            .value(value)
-            .build());
+        match self.fetch_resolved_flags(flags.clone(), &context).await {
+            Ok(resolved_flags) => {
+                if let (Some(cache), Some(key)) = (&self.cache, cache_key) {
+                    cache.insert(key, resolved_flags.clone());
+                }
+                Ok(FlagSnapshot::new(resolved_flags).with_applier(self.applier.clone()))
+            }
+            Err(e) => Err(EvaluationError::builder()
+                .message(format!("Failed to fetch resolved flags: {}", e))
+                .code(e.error_code())
+                .build()),
+        }
     }
 
     pub async fn get_flag<T: TypeConversionTrait>(
         &self,
         _flag_key: &str,
         default_value: T) -> Result<EvaluationDetails<T>, EvaluationError> {
-        let value = match self
-            .resolve_value(_flag_key, &self.context)
-            .await {
-            Ok(val) => val,
-            Err(e) => return Err(e),
-        };
-
-        if let Some(int_value) = value.value.as_type(&default_value) {
-            Ok(EvaluationDetails::builder()
-            .reason(value.reason.unwrap_or(EvaluationReason::Default))
-            .variant(value.variant.unwrap_or("unknown".to_string()))
-            .value(int_value)
-            .build())
-        } else {
-            let err = EvaluationError {
-                code: EvaluationErrorCode::TypeMismatch,
-                message: Some(format!("schema type is different for {_flag_key}").to_string())
-            };
-            Err(err)
-        }
+        self.resolve_flags(&[_flag_key])
+            .await?
+            .get_flag(_flag_key, default_value)
+    }
+
+    /// Resolve the flag and decode the value at `_flag_key` into `T` through serde.
+    pub async fn get_flag_as<T: DeserializeOwned>(
+        &self,
+        _flag_key: &str,
+        default_value: T) -> Result<EvaluationDetails<T>, EvaluationError> {
+        self.resolve_flags(&[_flag_key])
+            .await?
+            .get_flag_as(_flag_key, default_value)
+    }
+
+    /// Resolve the flag behind `T` and read it as a whole, falling back to
+    /// [`ConfidenceFlag::default_value`] for fields missing from the resolved value.
+    pub async fn get<T: ConfidenceFlag>(&self) -> Result<EvaluationDetails<T>, EvaluationError> {
+        self.resolve_flags(&[T::FLAG_NAME]).await?.get::<T>()
     }
 
 }
 
 #[cfg(test)]
 mod simple_error_tests;
+#[cfg(test)]
+mod test_server;
@@ -1,30 +1,246 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
 use crate::confidence_value::StructValue;
 use crate::confidence_value::ConfidenceValue;
+use crate::evaluation_error::EvaluationErrorCode;
 use typed_builder::TypedBuilder;
 
 #[derive(Debug)]
 pub enum ResolveError {
+    /// The request could not be sent or its response could not be read.
     NetworkError(reqwest::Error),
+    /// A request could not be encoded or a response could not be decoded.
     SerializationError,
-    // Add more variants for other custom errors if needed
+    /// The request did not complete within the configured timeout.
+    Timeout,
+    /// The client secret was rejected (`401` or `403`).
+    Unauthorized { status: u16, error: Option<ApiError> },
+    /// The request was rejected as malformed, e.g. because of an invalid evaluation context (`400`).
+    InvalidArgument { status: u16, error: Option<ApiError> },
+    /// Any other response with a non-success status.
+    Http { status: u16, error: Option<ApiError> },
+    /// A resolved flag value did not match its schema, e.g. `checkout.steps[1].title`.
+    InvalidValue { path: String, message: String },
 }
 
-#[derive(Clone)]
+/// The error body returned by the Confidence API along with a non-success status.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ApiError {
+    #[serde(default)]
+    pub code: i32,
+    #[serde(default)]
+    pub message: String,
+}
+
+impl ResolveError {
+    /// Classify a non-success response by its status and error body.
+    pub(crate) fn from_status(status: u16, body: &str) -> Self {
+        let error = serde_json::from_str::<ApiError>(body).ok();
+        match status {
+            400 => ResolveError::InvalidArgument { status, error },
+            401 | 403 => ResolveError::Unauthorized { status, error },
+            _ => ResolveError::Http { status, error },
+        }
+    }
+
+    /// The HTTP status of the response that caused this error, if there was one.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ResolveError::Unauthorized { status, .. }
+            | ResolveError::InvalidArgument { status, .. }
+            | ResolveError::Http { status, .. } => Some(*status),
+            ResolveError::NetworkError(err) => err.status().map(|status| status.as_u16()),
+            ResolveError::SerializationError
+            | ResolveError::Timeout
+            | ResolveError::InvalidValue { .. } => None,
+        }
+    }
+
+    /// The evaluation error code reported to callers for this error.
+    pub fn error_code(&self) -> EvaluationErrorCode {
+        match self {
+            ResolveError::NetworkError(_) => EvaluationErrorCode::General("NETWORK_ERROR".to_string()),
+            ResolveError::SerializationError | ResolveError::InvalidValue { .. } => {
+                EvaluationErrorCode::ParseError
+            }
+            ResolveError::Timeout => EvaluationErrorCode::General("TIMEOUT".to_string()),
+            ResolveError::Unauthorized { .. } => EvaluationErrorCode::ProviderNotReady,
+            ResolveError::InvalidArgument { .. } => EvaluationErrorCode::InvalidContext,
+            ResolveError::Http { status: 404, .. } => EvaluationErrorCode::FlagNotFound,
+            ResolveError::Http { status, .. } => EvaluationErrorCode::General(format!("HTTP_{status}")),
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (description, status, error) = match self {
+            ResolveError::NetworkError(err) => return write!(f, "network error: {err}"),
+            ResolveError::SerializationError => return f.write_str("failed to encode the request or decode the response"),
+            ResolveError::Timeout => return f.write_str("the request timed out"),
+            ResolveError::InvalidValue { path, message } => {
+                return write!(f, "invalid value at {path}: {message}")
+            }
+            ResolveError::Unauthorized { status, error } => ("the client secret was rejected", status, error),
+            ResolveError::InvalidArgument { status, error } => ("the request was rejected as invalid", status, error),
+            ResolveError::Http { status, error } => ("the request failed", status, error),
+        };
+        write!(f, "{description} (HTTP {status})")?;
+        match error {
+            Some(error) if !error.message.is_empty() => write!(f, ": {}", error.message),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResolveError::NetworkError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct APIConfig {
     pub api_key: String,
     pub region: Region,
+    /// Deadline for a single resolve, shared by all of its retries.
+    pub timeout: Option<Duration>,
+    /// Deadline for establishing a connection. Only applies to the HTTP client the SDK builds
+    /// itself; an injected client keeps its own connect timeout.
+    pub connect_timeout: Option<Duration>,
+    pub retry_policy: RetryPolicy,
+    /// What to do with resolved fields that are not described by the flag schema.
+    pub extra_fields: ExtraFieldPolicy,
+}
+
+/// How fields of a resolved value that are missing from the flag schema are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExtraFieldPolicy {
+    /// Leave the field out of the resolved value.
+    #[default]
+    Drop,
+    /// Keep the field, with its type inferred from the JSON value.
+    Keep,
+    /// Fail the resolve with [`ResolveError::InvalidValue`].
+    Reject,
+}
+
+impl APIConfig {
+    /// `http_config`, with the `connect_timeout` of this config unless it sets its own.
+    pub(crate) fn http_config(&self, http_config: &HttpConfig) -> HttpConfig {
+        HttpConfig {
+            connect_timeout: http_config.connect_timeout.or(self.connect_timeout),
+            ..http_config.clone()
+        }
+    }
+
+    pub(crate) fn build_http_client(&self, http_config: &HttpConfig) -> reqwest::Client {
+        self.http_config(http_config)
+            .build_client()
+            .expect("failed to build the HTTP client")
+    }
+}
+
+/// How failed requests to the resolver are retried.
+///
+/// Only failures that are safe to repeat are retried: connection errors, `5xx` responses and
+/// `429` responses. Waits between attempts grow exponentially with full jitter, unless the
+/// server asks for a specific delay through `Retry-After`; a `429` asking for more than
+/// `max_backoff` is returned without retrying.
+#[derive(Clone, Debug, PartialEq, TypedBuilder)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; `0` disables retrying.
+    #[builder(default = 2)]
+    pub max_retries: u32,
+    #[builder(default = Duration::from_millis(100))]
+    pub initial_backoff: Duration,
+    #[builder(default = Duration::from_secs(2))]
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::builder().build()
+    }
+}
+
+impl RetryPolicy {
+    /// A randomized wait before retry number `attempt`, counting from zero.
+    pub(crate) fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        ceiling.mul_f64(rand::random::<f64>())
+    }
+}
+
+/// Connection settings for the HTTP client shared by flag resolution and event publishing.
+#[derive(Clone, Debug, Default, TypedBuilder)]
+pub struct HttpConfig {
+    /// Upper bound on establishing a connection; falls back to [`APIConfig::connect_timeout`]
+    /// when set through `Confidence::builder().http_config(..)`.
+    #[builder(default, setter(strip_option))]
+    pub connect_timeout: Option<Duration>,
+    /// Maximum number of idle connections kept per host.
+    #[builder(default, setter(strip_option))]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How long an idle pooled connection is kept open.
+    #[builder(default, setter(strip_option))]
+    pub pool_idle_timeout: Option<Duration>,
+    /// Interval of TCP keep-alive probes on open connections.
+    #[builder(default, setter(strip_option))]
+    pub tcp_keepalive: Option<Duration>,
+    /// Speak HTTP/2 without negotiating it first.
+    #[builder(default)]
+    pub http2_prior_knowledge: bool,
+}
+
+impl HttpConfig {
+    /// Build a client with these settings, e.g. to share it beyond `Confidence`; pass settings to
+    /// `Confidence::builder().http_config(..)` to let it build its own.
+    pub fn build_client(&self) -> Result<reqwest::Client, reqwest::Error> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(max_idle) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(idle_timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(idle_timeout);
+        }
+        if let Some(keepalive) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(keepalive);
+        }
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        builder.build()
+    }
 }
 
 #[allow(unused_variables)]
-#[derive(Clone)]
+#[derive(Clone, Default)]
 #[derive(Debug, PartialEq)]
 pub enum Region {
     US,
     EU,
+    #[default]
     Global,
+    /// Send resolve and event traffic to arbitrary base URLs, e.g. an egress proxy or a local
+    /// stand-in such as `http://localhost:8080`.
+    Custom {
+        resolver_url: String,
+        events_url: String,
+    },
 }
 
 #[allow(unused_variables)]
@@ -64,82 +280,169 @@ pub struct ResolvedFlags {
 pub struct ResolvedFlag {
     pub flag: String,
     pub variant: String,
-    pub value: StructValue,
+    /// `None` when the resolve assigned no value, e.g. because no segment matched.
+    pub value: Option<StructValue>,
     pub reason: String,
 }
 
-trait FlagValueConversion<T> {
-    fn into_value(self, schema: &Option<FlagSchema>) -> T;
-}
-
-impl FlagValueConversion<StructValue> for Option<Value> {
-    fn into_value(self, schema: &Option<FlagSchema>) -> StructValue {
-        if let Some(schema) = schema {
-            let schema = &schema.schema;
-            match self {
-                Some(value) => {
-                    if let Value::Object(value_map) = value {
-                        let new_map: HashMap<String, ConfidenceValue> = value_map
-                            .into_iter()
-                            .map(|(key, value)| {
-                                let converted_value = match schema[&key].clone() {
-                                    SchemaType::BoolType => ConfidenceValue::Bool(
-                                        value.as_bool().unwrap_or_default(),
-                                    ),
-                                    SchemaType::IntType => {
-                                        ConfidenceValue::Int(value.as_i64().unwrap_or_default())
-                                    }
-                                    SchemaType::DoubleType => ConfidenceValue::Float(
-                                        value.as_f64().unwrap_or_default(),
-                                    ),
-                                    SchemaType::StringType => ConfidenceValue::String(
-                                        value.as_str().unwrap_or_default().to_string(),
-                                    ),
-                                    SchemaType::StructType(struct_value) => {
-                                        ConfidenceValue::Struct(Some(value).into_value(&Some(
-                                            FlagSchema {
-                                                schema: *struct_value,
-                                            },
-                                        )))
-                                    }
-                                };
-                                (key, converted_value)
-                            })
-                            .collect();
-                        StructValue { fields: new_map }
-                    } else {
-                        StructValue::default()
-                    }
-                }
-                None => StructValue::default(),
+impl NetworkResolvedFlag {
+    /// Convert the JSON value of the flag into typed values according to its schema.
+    pub fn into_resolved_flag(self, extra_fields: ExtraFieldPolicy) -> Result<ResolvedFlag, ResolveError> {
+        let path = self.flag.trim_start_matches("flags/").to_string();
+        let value = match (self.value, self.flag_schema) {
+            (None | Some(Value::Null), _) => None,
+            (Some(value), Some(flag_schema)) => {
+                Some(convert_struct(value, &flag_schema.schema, extra_fields, &path)?)
             }
-        } else {
-            StructValue::default()
-        }
-    }
-}
-
-impl Into<ResolvedFlag> for NetworkResolvedFlag {
-    fn into(self) -> ResolvedFlag {
-        ResolvedFlag {
+            (Some(value), None) => Some(convert_struct(value, &HashMap::new(), extra_fields, &path)?),
+        };
+        Ok(ResolvedFlag {
             flag: self.flag,
             variant: self.variant,
-            value: self.value.into_value(&self.flag_schema),
+            value,
             reason: self.reason,
-        }
+        })
     }
 }
 
-impl Into<ResolvedFlags> for NetworkResolvedFlags {
-    fn into(self) -> ResolvedFlags {
-        ResolvedFlags {
+impl NetworkResolvedFlags {
+    /// Convert every flag with [`NetworkResolvedFlag::into_resolved_flag`].
+    pub fn into_resolved_flags(self, extra_fields: ExtraFieldPolicy) -> Result<ResolvedFlags, ResolveError> {
+        Ok(ResolvedFlags {
             resolve_token: self.resolve_token,
             flags: self
                 .resolve_flags
                 .into_iter()
-                .map(|flag| flag.into())
-                .collect(),
+                .map(|flag| flag.into_resolved_flag(extra_fields))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl TryFrom<NetworkResolvedFlag> for ResolvedFlag {
+    type Error = ResolveError;
+
+    fn try_from(flag: NetworkResolvedFlag) -> Result<Self, Self::Error> {
+        flag.into_resolved_flag(ExtraFieldPolicy::default())
+    }
+}
+
+impl TryFrom<NetworkResolvedFlags> for ResolvedFlags {
+    type Error = ResolveError;
+
+    fn try_from(flags: NetworkResolvedFlags) -> Result<Self, Self::Error> {
+        flags.into_resolved_flags(ExtraFieldPolicy::default())
+    }
+}
+
+fn convert_struct(
+    value: Value,
+    schema: &HashMap<String, SchemaType>,
+    extra_fields: ExtraFieldPolicy,
+    path: &str,
+) -> Result<StructValue, ResolveError> {
+    let value_map = match value {
+        Value::Object(value_map) => value_map,
+        other => return Err(invalid_value(path, "struct", &other)),
+    };
+
+    let mut fields = HashMap::new();
+    for (key, value) in value_map {
+        let field_path = format!("{path}.{key}");
+        let converted_value = match (schema.get(&key), extra_fields) {
+            (Some(schema_type), _) => convert_value(value, schema_type, extra_fields, &field_path)?,
+            (None, ExtraFieldPolicy::Drop) => continue,
+            (None, ExtraFieldPolicy::Keep) => infer_value(value, &field_path)?,
+            (None, ExtraFieldPolicy::Reject) => {
+                return Err(ResolveError::InvalidValue {
+                    path: field_path,
+                    message: "the field is not in the flag schema".to_string(),
+                })
+            }
+        };
+        fields.insert(key, converted_value);
+    }
+    Ok(StructValue { fields })
+}
+
+fn convert_value(
+    value: Value,
+    schema_type: &SchemaType,
+    extra_fields: ExtraFieldPolicy,
+    path: &str,
+) -> Result<ConfidenceValue, ResolveError> {
+    match (schema_type, value) {
+        // Properties the variant does not set are sent as null, whatever their schema.
+        (_, Value::Null) => Ok(ConfidenceValue::Null),
+        (SchemaType::BoolType, Value::Bool(value)) => Ok(ConfidenceValue::Bool(value)),
+        (SchemaType::IntType, Value::Number(number)) => match as_int(&number) {
+            Some(value) => Ok(ConfidenceValue::Int(value)),
+            None => Err(invalid_value(path, "int", &Value::Number(number))),
+        },
+        (SchemaType::DoubleType, Value::Number(number)) => match number.as_f64() {
+            Some(value) => Ok(ConfidenceValue::Float(value)),
+            None => Err(invalid_value(path, "float", &Value::Number(number))),
+        },
+        (SchemaType::StringType, Value::String(value)) => Ok(ConfidenceValue::String(value)),
+        (SchemaType::StructType(schema), value) => {
+            convert_struct(value, schema, extra_fields, path).map(ConfidenceValue::Struct)
+        }
+        (SchemaType::ListType(element_type), Value::Array(items)) => items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| {
+                convert_value(item, element_type, extra_fields, &format!("{path}[{index}]"))
+            })
+            .collect::<Result<_, _>>()
+            .map(ConfidenceValue::Array),
+        (schema_type, value) => Err(invalid_value(path, schema_type.type_name(), &value)),
+    }
+}
+
+// Values of fields missing from the schema keep the type of their JSON value.
+fn infer_value(value: Value, path: &str) -> Result<ConfidenceValue, ResolveError> {
+    match value {
+        Value::Bool(value) => Ok(ConfidenceValue::Bool(value)),
+        Value::Number(number) => Ok(match number.as_i64() {
+            Some(value) => ConfidenceValue::Int(value),
+            None => ConfidenceValue::Float(number.as_f64().unwrap_or_default()),
+        }),
+        Value::String(value) => Ok(ConfidenceValue::String(value)),
+        Value::Array(items) => items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| infer_value(item, &format!("{path}[{index}]")))
+            .collect::<Result<_, _>>()
+            .map(ConfidenceValue::Array),
+        Value::Object(_) => {
+            convert_struct(value, &HashMap::new(), ExtraFieldPolicy::Keep, path).map(ConfidenceValue::Struct)
         }
+        Value::Null => Ok(ConfidenceValue::Null),
+    }
+}
+
+// Integers may arrive as whole doubles, since the value is sent as a protobuf `Struct`.
+fn as_int(number: &serde_json::Number) -> Option<i64> {
+    number.as_i64().or_else(|| {
+        number
+            .as_f64()
+            .filter(|value| value.fract() == 0.0 && value.abs() < i64::MAX as f64)
+            .map(|value| value as i64)
+    })
+}
+
+fn invalid_value(path: &str, expected: &str, found: &Value) -> ResolveError {
+    let found = match found {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => "bool".to_string(),
+        Value::Number(number) => format!("number {number}"),
+        Value::String(_) => "string".to_string(),
+        Value::Array(_) => "array".to_string(),
+        Value::Object(_) => "struct".to_string(),
+    };
+    ResolveError::InvalidValue {
+        path: path.to_string(),
+        message: format!("expected {expected}, found {found}"),
     }
 }
 
@@ -151,10 +454,26 @@ pub enum SchemaType {
     StringType,
     BoolType,
     StructType(Box<HashMap<String, SchemaType>>),
+    /// A list whose elements all have the given type.
+    ListType(Box<SchemaType>),
+}
+
+impl SchemaType {
+    fn type_name(&self) -> &'static str {
+        match self {
+            SchemaType::IntType => "int",
+            SchemaType::DoubleType => "float",
+            SchemaType::StringType => "string",
+            SchemaType::BoolType => "bool",
+            SchemaType::StructType(_) => "struct",
+            SchemaType::ListType(_) => "array",
+        }
+    }
 }
 
 pub trait APIURL {
     fn url(&self) -> String;
+    fn events_url(&self) -> String;
 }
 
 impl APIURL for Region {
@@ -163,6 +482,14 @@ impl APIURL for Region {
             Region::EU => "https://resolver.eu.confidence.dev".to_string(),
             Region::US => "https://resolver.us.confidence.dev".to_string(),
             Region::Global => "https://resolver.confidence.dev".to_string(),
+            Region::Custom { resolver_url, .. } => resolver_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn events_url(&self) -> String {
+        match self {
+            Region::Custom { events_url, .. } => events_url.trim_end_matches('/').to_string(),
+            _ => "https://events.confidence.dev".to_string(),
         }
     }
 }
@@ -189,8 +516,243 @@ pub struct ResolveRequest {
     flags: Vec<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, TypedBuilder)]
+pub struct AppliedFlag {
+    #[builder(setter(into))]
+    pub flag: String,
+    #[builder(setter(into))]
+    #[serde(rename = "applyTime")]
+    pub apply_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, TypedBuilder)]
+pub struct ApplyRequest {
+    #[builder(setter(into))]
+    #[serde(rename = "clientSecret")]
+    client_secret: String,
+    #[builder(setter(into))]
+    #[serde(rename = "resolveToken")]
+    resolve_token: String,
+    #[builder(setter(into))]
+    flags: Vec<AppliedFlag>,
+    #[builder(setter(into))]
+    #[serde(rename = "sendTime")]
+    send_time: DateTime<Utc>,
+    #[builder(setter(into))]
+    sdk: SDK,
+}
+
 impl From<reqwest::Error> for ResolveError {
     fn from(error: reqwest::Error) -> ResolveError {
-        ResolveError::NetworkError(error)
+        if error.is_timeout() {
+            ResolveError::Timeout
+        } else {
+            ResolveError::NetworkError(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::confidence_value::StructValue;
+    use crate::evaluation_error::EvaluationErrorCode;
+    use crate::models::{
+        ExtraFieldPolicy, NetworkResolvedFlag, NetworkResolvedFlags, ResolveError, ResolvedFlags,
+        RetryPolicy,
+    };
+    use crate::event_sender::EventSender;
+    use crate::test_server::TestServer;
+    use crate::{APIConfig, Confidence, ConfidenceValue, FlagSnapshot, HttpConfig, Region};
+
+    #[test]
+    fn http_config_builds_client() {
+        let client = HttpConfig::builder()
+            .pool_max_idle_per_host(4)
+            .pool_idle_timeout(Duration::from_secs(30))
+            .tcp_keepalive(Duration::from_secs(60))
+            .http2_prior_knowledge(true)
+            .build()
+            .build_client();
+
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn retry_backoff_is_capped() {
+        let policy = RetryPolicy::builder()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(250))
+            .build();
+
+        for attempt in 0..10 {
+            assert!(policy.backoff(attempt) <= Duration::from_millis(250));
+        }
+        assert!(policy.backoff(0) <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn resolve_errors_are_classified_by_status() {
+        let body = r#"{"code": 16, "message": "client secret not found"}"#;
+        let error = ResolveError::from_status(401, body);
+
+        assert_eq!(error.status(), Some(401));
+        assert_eq!(error.error_code(), EvaluationErrorCode::ProviderNotReady);
+        assert_eq!(error.to_string(), "the client secret was rejected (HTTP 401): client secret not found");
+
+        assert_eq!(ResolveError::from_status(400, "").error_code(), EvaluationErrorCode::InvalidContext);
+        assert_eq!(
+            ResolveError::from_status(503, "").error_code(),
+            EvaluationErrorCode::General("HTTP_503".to_string())
+        );
+        assert_eq!(ResolveError::SerializationError.error_code(), EvaluationErrorCode::ParseError);
+    }
+
+    #[test]
+    fn http_config_falls_back_to_the_api_connect_timeout() {
+        let api_config = APIConfig { connect_timeout: Some(Duration::from_secs(3)), ..Default::default() };
+
+        let inherited = api_config.http_config(&HttpConfig::builder().pool_max_idle_per_host(4).build());
+        let own = api_config.http_config(&HttpConfig::builder().connect_timeout(Duration::from_secs(1)).build());
+
+        assert_eq!(inherited.connect_timeout, Some(Duration::from_secs(3)));
+        assert_eq!(inherited.pool_max_idle_per_host, Some(4));
+        assert_eq!(own.connect_timeout, Some(Duration::from_secs(1)));
+    }
+
+    #[tokio::test]
+    async fn resolves_and_events_share_the_injected_client() {
+        let server = TestServer::start(vec![("200 OK", r#"{"resolvedFlags": [], "resolveToken": "token"}"#)]).await;
+        let client = reqwest::Client::builder()
+            .default_headers(reqwest::header::HeaderMap::from_iter([(
+                reqwest::header::HeaderName::from_static("x-client"),
+                reqwest::header::HeaderValue::from_static("injected"),
+            )]))
+            .build()
+            .unwrap();
+        let confidence = Confidence::builder()
+            .api_config(APIConfig {
+                region: Region::Custom { resolver_url: server.url.clone(), events_url: server.url.clone() },
+                ..Default::default()
+            })
+            .http_client(client)
+            .build();
+
+        confidence.resolve_all().await.unwrap();
+        confidence.track("navigate", HashMap::new());
+        confidence.flush_events().await;
+
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        for request in received {
+            assert!(request.head.contains("x-client: injected"), "{} used another client", request.path);
+        }
+    }
+
+    #[test]
+    fn list_schemas_convert_to_typed_arrays() {
+        let json_data = r#"
+        {
+          "resolvedFlags": [
+            {
+              "flag": "flags/checkout",
+              "variant": "flags/checkout/variants/on",
+              "value": { "limits": [1, 2, 3], "steps": [{ "title": "cart" }, { "title": "pay" }] },
+              "flagSchema": { "schema": {
+                "limits": { "listSchema": { "elementSchema": { "intSchema": {} } } },
+                "steps": { "listSchema": { "elementSchema": { "structSchema": { "schema": {
+                  "title": { "stringSchema": {} }
+                } } } } }
+              } },
+              "reason": "RESOLVE_REASON_MATCH"
+            }
+          ],
+          "resolveToken": "token"
+        }"#;
+        let network_flags: NetworkResolvedFlags = serde_json::from_str(json_data).unwrap();
+        let resolved_flags: ResolvedFlags = network_flags.try_into().unwrap();
+
+        assert_eq!(
+            resolved_flags.flags[0].value.as_ref().unwrap().fields["steps"],
+            ConfidenceValue::Array(vec![
+                StructValue::default().with_field("title", "cart").into(),
+                StructValue::default().with_field("title", "pay").into(),
+            ])
+        );
+
+        let snapshot = FlagSnapshot::new(resolved_flags);
+        assert_eq!(snapshot.get_flag("checkout.limits", Vec::<i64>::new()).unwrap().value, vec![1, 2, 3]);
+        assert_eq!(
+            snapshot.get_flag("checkout.limits", Vec::<String>::new()).unwrap_err().code,
+            EvaluationErrorCode::TypeMismatch
+        );
+    }
+
+    fn network_flag(value: &str) -> NetworkResolvedFlag {
+        let json_data = r#"
+        {
+          "flag": "flags/checkout",
+          "variant": "flags/checkout/variants/on",
+          "value": {value},
+          "flagSchema": { "schema": {
+            "limit": { "intSchema": {} },
+            "steps": { "listSchema": { "elementSchema": { "structSchema": { "schema": {
+              "title": { "stringSchema": {} }
+            } } } } }
+          } },
+          "reason": "RESOLVE_REASON_MATCH"
+        }"#
+        .replace("{value}", value);
+        serde_json::from_str(&json_data).unwrap()
+    }
+
+    #[test]
+    fn mismatched_values_report_their_path() {
+        let flag = network_flag(r#"{ "limit": 3, "steps": [{ "title": "cart" }, { "title": 7 }] }"#);
+
+        let error = flag.into_resolved_flag(ExtraFieldPolicy::Drop).unwrap_err();
+
+        assert_eq!(error.error_code(), EvaluationErrorCode::ParseError);
+        assert_eq!(
+            error.to_string(),
+            "invalid value at checkout.steps[1].title: expected string, found number 7"
+        );
+    }
+
+    #[test]
+    fn whole_doubles_are_ints_and_nulls_are_kept() {
+        let flag = network_flag(r#"{ "limit": 3.0, "steps": null }"#);
+
+        let resolved = flag.into_resolved_flag(ExtraFieldPolicy::Drop).unwrap();
+
+        assert_eq!(
+            resolved.value,
+            Some(
+                StructValue::default()
+                    .with_field("limit", 3)
+                    .with_field("steps", ConfidenceValue::Null)
+            )
+        );
+        assert!(network_flag(r#"{ "limit": 3.5 }"#)
+            .into_resolved_flag(ExtraFieldPolicy::Drop)
+            .is_err());
+    }
+
+    #[test]
+    fn extra_fields_follow_the_policy() {
+        let value = r#"{ "limit": 3, "color": "blue" }"#;
+
+        let dropped = network_flag(value).into_resolved_flag(ExtraFieldPolicy::Drop).unwrap();
+        assert!(!dropped.value.unwrap().fields.contains_key("color"));
+
+        let kept = network_flag(value).into_resolved_flag(ExtraFieldPolicy::Keep).unwrap();
+        assert_eq!(kept.value.unwrap().fields["color"], ConfidenceValue::from("blue"));
+
+        match network_flag(value).into_resolved_flag(ExtraFieldPolicy::Reject) {
+            Err(ResolveError::InvalidValue { path, .. }) => assert_eq!(path, "checkout.color"),
+            other => panic!("expected an invalid value error, got {other:?}"),
+        }
     }
 }
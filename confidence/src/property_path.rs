@@ -0,0 +1,197 @@
+use std::fmt;
+
+use crate::confidence_value::ConfidenceValue;
+
+/// One step of a property path: a struct key or an array index.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A malformed property path, e.g. `a..b` or `a[x]`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PathError {
+    path: String,
+    message: &'static str,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid property path {:?}: {}", self.path, self.message)
+    }
+}
+
+/// Parse a path such as `a.b[2].c`.
+///
+/// Keys are separated by `.` and followed by any number of `[index]` suffixes. A `\` escapes the
+/// next character, so `a\.b` is the single key `a.b`.
+pub(crate) fn parse(path: &str) -> Result<Vec<PathSegment>, PathError> {
+    let error = |message| PathError { path: path.to_string(), message };
+    let mut segments = Vec::new();
+    let mut key = String::new();
+    // Whether `key` is being read, as opposed to just after an index.
+    let mut in_key = true;
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                key.push(chars.next().ok_or_else(|| error("dangling escape"))?);
+                in_key = true;
+            }
+            '.' => {
+                if in_key {
+                    if key.is_empty() {
+                        return Err(error("empty key"));
+                    }
+                    segments.push(PathSegment::Key(std::mem::take(&mut key)));
+                }
+                in_key = true;
+            }
+            '[' => {
+                if !key.is_empty() {
+                    segments.push(PathSegment::Key(std::mem::take(&mut key)));
+                } else if in_key && !segments.is_empty() {
+                    return Err(error("empty key"));
+                }
+                let mut index = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(digit) if digit.is_ascii_digit() => index.push(digit),
+                        Some(_) => return Err(error("array index is not a number")),
+                        None => return Err(error("unterminated array index")),
+                    }
+                }
+                let index = index.parse().map_err(|_| error("array index is not a number"))?;
+                segments.push(PathSegment::Index(index));
+                in_key = false;
+            }
+            c => {
+                if !in_key {
+                    return Err(error("expected `.` or `[` after an array index"));
+                }
+                key.push(c);
+            }
+        }
+    }
+
+    if !key.is_empty() {
+        segments.push(PathSegment::Key(key));
+    } else if in_key && !path.is_empty() {
+        return Err(error("empty key"));
+    }
+    Ok(segments)
+}
+
+/// Format `segments` back into a path, escaping keys as needed.
+pub(crate) fn format(segments: &[PathSegment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        match segment {
+            PathSegment::Key(key) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                for c in key.chars() {
+                    if matches!(c, '.' | '[' | '\\') {
+                        path.push('\\');
+                    }
+                    path.push(c);
+                }
+            }
+            PathSegment::Index(index) => path.push_str(&format!("[{index}]")),
+        }
+    }
+    path
+}
+
+/// The flag name a flag key such as `my-flag.property[0]` starts with, unescaped, e.g. `a.b`
+/// for `a\.b.c`; `None` if the key is not a valid path starting with a name.
+pub(crate) fn flag_name(flag_key: &str) -> Option<String> {
+    match parse(flag_key).ok()?.into_iter().next()? {
+        PathSegment::Key(name) => Some(name),
+        PathSegment::Index(_) => None,
+    }
+}
+
+/// The key of the whole flag a flag key starts with, still escaped, e.g. `a\.b` for `a\.b.c`.
+pub(crate) fn whole_flag_key(flag_key: &str) -> Option<String> {
+    flag_name(flag_key).map(|name| format(&[PathSegment::Key(name)]))
+}
+
+/// The value one `segment` below `value`, if there is one.
+pub(crate) fn step<'a>(value: &'a ConfidenceValue, segment: &PathSegment) -> Option<&'a ConfidenceValue> {
+    match (value, segment) {
+        (ConfidenceValue::Struct(value), PathSegment::Key(key)) => value.fields.get(key),
+        (ConfidenceValue::Array(values), PathSegment::Index(index)) => values.get(*index),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::confidence_value::{ConfidenceValue, StructValue};
+    use crate::property_path::{flag_name, format, parse, whole_flag_key, PathSegment};
+
+    fn key(key: &str) -> PathSegment {
+        PathSegment::Key(key.to_string())
+    }
+
+    #[test]
+    fn parses_keys_indices_and_escapes() {
+        assert_eq!(
+            parse("a.b[2][0].c").unwrap(),
+            vec![key("a"), key("b"), PathSegment::Index(2), PathSegment::Index(0), key("c")]
+        );
+        assert_eq!(parse(r"version\.2.x").unwrap(), vec![key("version.2"), key("x")]);
+        assert_eq!(parse("[1]").unwrap(), vec![PathSegment::Index(1)]);
+        assert_eq!(parse("").unwrap(), vec![]);
+
+        for invalid in ["a..b", ".a", "a.", "a[x]", "a[1", "a[1]b", "a.[1]", r"a\"] {
+            assert!(parse(invalid).is_err(), "{invalid} should not parse");
+        }
+    }
+
+    #[test]
+    fn format_round_trips() {
+        for path in ["a.b[2].c", r"version\.2.x", r"back\\slash", "[0][1]"] {
+            assert_eq!(format(&parse(path).unwrap()), path);
+        }
+    }
+
+    #[test]
+    fn flag_names() {
+        assert_eq!(flag_name("flag.a.b").unwrap(), "flag");
+        assert_eq!(flag_name("flag[0]").unwrap(), "flag");
+        assert_eq!(flag_name("flag").unwrap(), "flag");
+        assert_eq!(flag_name(r"a\.b.c").unwrap(), "a.b");
+        assert_eq!(whole_flag_key(r"a\.b.c").unwrap(), r"a\.b");
+        assert_eq!(flag_name("[0].a"), None);
+        assert_eq!(flag_name("a..b"), None);
+    }
+
+    #[test]
+    fn looks_up_values() {
+        let value = ConfidenceValue::Struct(
+            StructValue::default()
+                .with_field("version.2", StructValue::default().with_field("enabled", true))
+                .with_field(
+                    "steps",
+                    vec![ConfidenceValue::from(StructValue::default().with_field("title", "cart"))],
+                ),
+        );
+
+        assert_eq!(value.get_path("steps[0].title"), Some(&ConfidenceValue::from("cart")));
+        assert_eq!(value.get_path(r"version\.2.enabled"), Some(&ConfidenceValue::Bool(true)));
+        assert_eq!(value.get_path("steps[1].title"), None);
+        assert_eq!(value.get_path("steps.title"), None);
+        assert_eq!(value.get_path("steps..title"), None);
+        assert_eq!(value.get_path(""), Some(&value));
+        assert_eq!(
+            value.as_struct().unwrap().pointer("steps[0]").and_then(|step| step.get_path("title")),
+            Some(&ConfidenceValue::from("cart"))
+        );
+    }
+}
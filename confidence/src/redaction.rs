@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::confidence_value::StructValue;
+use crate::conversion_trait::ToSerdeValueConverter;
+use crate::property_path::{self, PathSegment};
+use crate::ConfidenceValue;
+
+/// What happens to a context value matched by a redaction rule.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Redaction {
+    /// Leave the key out.
+    Drop,
+    /// Replace the value with the hex-encoded SHA-256 of `salt` followed by the value; strings are
+    /// hashed as they are, other values in their JSON encoding.
+    Hash { salt: String },
+    /// Keep at most this many characters of a string; other values are kept as they are.
+    Truncate(usize),
+}
+
+/// Rules applied to the evaluation context before it leaves the process.
+///
+/// Patterns are property paths such as `user.email`, in which `*` matches any part of a key,
+/// e.g. `*_email` or `device.*`, and `[*]` matches any element of a list, e.g.
+/// `contacts[*].email`. A pattern that is not a valid path only matches a top-level key spelled
+/// exactly like it. The first rule matching a value applies to all of it; values inside a struct
+/// or list no rule matches are checked one by one.
+#[derive(Clone, Debug, Default)]
+pub struct RedactionRules {
+    allow: Option<Vec<Vec<PatternSegment>>>,
+    rules: Vec<(Vec<PatternSegment>, Redaction)>,
+}
+
+/// One step of a pattern: like a [`PathSegment`], with wildcards.
+#[derive(Clone, Debug, PartialEq)]
+enum PatternSegment {
+    /// A struct key, in which `*` matches any part of the key.
+    Key(String),
+    Index(usize),
+    /// `[*]`, matching any list index.
+    AnyIndex,
+}
+
+impl From<PathSegment> for PatternSegment {
+    fn from(segment: PathSegment) -> Self {
+        match segment {
+            PathSegment::Key(key) => PatternSegment::Key(key),
+            PathSegment::Index(index) => PatternSegment::Index(index),
+        }
+    }
+}
+
+impl RedactionRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only send values matching one of `patterns`, with the structs containing them.
+    pub fn allow(mut self, patterns: &[&str]) -> Self {
+        self.allow = Some(patterns.iter().map(|pattern| parse_pattern(pattern)).collect());
+        self
+    }
+
+    pub fn rule(mut self, pattern: &str, redaction: Redaction) -> Self {
+        self.rules.push((parse_pattern(pattern), redaction));
+        self
+    }
+
+    /// The copy of `context` that may be sent.
+    pub fn apply(&self, context: &HashMap<String, ConfidenceValue>) -> HashMap<String, ConfidenceValue> {
+        let mut path = Vec::new();
+        context
+            .iter()
+            .filter_map(|(key, value)| {
+                self.redact_field(&mut path, PathSegment::Key(key.clone()), value)
+                    .map(|value| (key.clone(), value))
+            })
+            .collect()
+    }
+
+    fn redact_field(
+        &self,
+        path: &mut Vec<PathSegment>,
+        segment: PathSegment,
+        value: &ConfidenceValue,
+    ) -> Option<ConfidenceValue> {
+        path.push(segment);
+        let redacted = self.redact_value(path, value);
+        path.pop();
+        redacted
+    }
+
+    fn redact_value(&self, path: &mut Vec<PathSegment>, value: &ConfidenceValue) -> Option<ConfidenceValue> {
+        let allowed_whole = match &self.allow {
+            None => true,
+            Some(allow) => {
+                if allow.iter().any(|pattern| matches(pattern, path)) {
+                    true
+                } else if allow.iter().any(|pattern| pattern.len() > path.len() && matches(&pattern[..path.len()], path)) {
+                    false
+                } else {
+                    return None;
+                }
+            }
+        };
+
+        if let Some((_, redaction)) = self.rules.iter().find(|(pattern, _)| matches(pattern, path)) {
+            return apply_redaction(redaction, value);
+        }
+        match value {
+            ConfidenceValue::Struct(value) => {
+                let fields = value
+                    .fields
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        self.redact_field(path, PathSegment::Key(key.clone()), value)
+                            .map(|value| (key.clone(), value))
+                    })
+                    .collect();
+                Some(ConfidenceValue::Struct(StructValue { fields }))
+            }
+            ConfidenceValue::Array(values) => {
+                let values = values
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, value)| self.redact_field(path, PathSegment::Index(index), value))
+                    .collect();
+                Some(ConfidenceValue::Array(values))
+            }
+            // Only structs and lists can hold allowed values further down.
+            _ if !allowed_whole => None,
+            value => Some(value.clone()),
+        }
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<PatternSegment> {
+    parse_wildcard_path(pattern).unwrap_or_else(|| vec![PatternSegment::Key(pattern.to_string())])
+}
+
+// A property path in which `[*]` stands for any index.
+fn parse_wildcard_path(pattern: &str) -> Option<Vec<PatternSegment>> {
+    let mut segments = Vec::new();
+    for (position, part) in split_any_index(pattern).into_iter().enumerate() {
+        let part = if position == 0 {
+            part
+        } else {
+            segments.push(PatternSegment::AnyIndex);
+            // After `[*]` comes the end of the pattern, another index or `.` and a key.
+            match part.strip_prefix('.') {
+                Some(rest) => rest,
+                None if part.is_empty() || part.starts_with('[') => part,
+                None => return None,
+            }
+        };
+        let parsed = property_path::parse(part).ok()?;
+        segments.extend(parsed.into_iter().map(PatternSegment::from));
+    }
+    Some(segments)
+}
+
+// Split `pattern` around every `[*]` that is not escaped.
+fn split_any_index(pattern: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (position, c) in pattern.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if position >= start && pattern[position..].starts_with("[*]") {
+            parts.push(&pattern[start..position]);
+            start = position + "[*]".len();
+        }
+    }
+    parts.push(&pattern[start..]);
+    parts
+}
+
+fn matches(pattern: &[PatternSegment], path: &[PathSegment]) -> bool {
+    pattern.len() == path.len()
+        && pattern.iter().zip(path).all(|(pattern, segment)| match (pattern, segment) {
+            (PatternSegment::Key(pattern), PathSegment::Key(key)) => glob_matches(pattern, key),
+            (PatternSegment::Index(pattern), PathSegment::Index(index)) => pattern == index,
+            (PatternSegment::AnyIndex, PathSegment::Index(_)) => true,
+            _ => false,
+        })
+}
+
+fn glob_matches(pattern: &str, key: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == key,
+        Some((prefix, rest)) => {
+            let Some(key) = key.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=key.len())
+                .filter(|start| key.is_char_boundary(*start))
+                .any(|start| glob_matches(rest, &key[start..]))
+        }
+    }
+}
+
+fn apply_redaction(redaction: &Redaction, value: &ConfidenceValue) -> Option<ConfidenceValue> {
+    match redaction {
+        Redaction::Drop => None,
+        Redaction::Hash { salt } => {
+            let mut hasher = Sha256::new();
+            hasher.update(salt.as_bytes());
+            match value {
+                ConfidenceValue::String(value) => hasher.update(value.as_bytes()),
+                value => hasher.update(value.clone().convert().to_string().as_bytes()),
+            }
+            let digest: String = hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect();
+            Some(ConfidenceValue::String(digest))
+        }
+        Redaction::Truncate(max_chars) => match value {
+            ConfidenceValue::String(value) => Some(ConfidenceValue::String(value.chars().take(*max_chars).collect())),
+            value => Some(value.clone()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use crate::confidence_value::StructValue;
+    use crate::event_sender::EventSender;
+    use crate::models::ResolvedFlags;
+    use crate::redaction::{Redaction, RedactionRules};
+    use crate::resolve::MockNetworkFlagResolver;
+    use crate::test_server::TestServer;
+    use crate::{APIConfig, Confidence, ConfidenceValue, Region};
+
+    fn context() -> HashMap<String, ConfidenceValue> {
+        HashMap::from([
+            ("targeting_key".to_string(), ConfidenceValue::from("user-1")),
+            ("email".to_string(), ConfidenceValue::from("jane@example.com")),
+            ("bio".to_string(), ConfidenceValue::from("likes long walks")),
+            (
+                "device".to_string(),
+                StructValue::default()
+                    .with_field("os", "ios")
+                    .with_field("ad_id", "a-1")
+                    .with_field("vendor_id", "v-1")
+                    .into(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn rules_drop_hash_and_truncate() {
+        let salted = |salt: &str| {
+            RedactionRules::new()
+                .rule("targeting_key", Redaction::Hash { salt: salt.to_string() })
+                .rule("email", Redaction::Drop)
+                .rule("bio", Redaction::Truncate(5))
+                .rule("device.*_id", Redaction::Drop)
+                .apply(&context())
+        };
+
+        let redacted = salted("pepper");
+
+        assert!(!redacted.contains_key("email"));
+        assert_eq!(redacted["bio"], ConfidenceValue::from("likes"));
+        assert_eq!(redacted["device"], StructValue::default().with_field("os", "ios").into());
+        let hash = redacted["targeting_key"].as_str().unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(salted("pepper")["targeting_key"], redacted["targeting_key"]);
+        assert_ne!(salted("salt")["targeting_key"], redacted["targeting_key"]);
+    }
+
+    #[test]
+    fn allow_list_keeps_only_matching_values() {
+        let redacted = RedactionRules::new()
+            .allow(&["targeting_key", "device.os"])
+            .rule("targeting_key", Redaction::Truncate(4))
+            .apply(&context());
+
+        assert_eq!(
+            redacted,
+            HashMap::from([
+                ("targeting_key".to_string(), ConfidenceValue::from("user")),
+                ("device".to_string(), StructValue::default().with_field("os", "ios").into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn rules_reach_into_lists() {
+        let contact = |name: &str| StructValue::default().with_field("name", name).with_field("email", "a@b.c");
+        let context = HashMap::from([(
+            "contacts".to_string(),
+            ConfidenceValue::from(vec![contact("jane"), contact("joe")]),
+        )]);
+
+        let redacted = RedactionRules::new()
+            .rule("contacts[*].email", Redaction::Drop)
+            .rule("contacts[1].name", Redaction::Truncate(1))
+            .apply(&context);
+
+        assert_eq!(
+            redacted["contacts"],
+            ConfidenceValue::from(vec![
+                StructValue::default().with_field("name", "jane"),
+                StructValue::default().with_field("name", "j"),
+            ])
+        );
+        let allowed = RedactionRules::new().allow(&["contacts[*].name"]).apply(&context);
+        assert_eq!(allowed["contacts"].get_path("[0].email"), None);
+        assert_eq!(allowed["contacts"].get_path("[0].name"), Some(&ConfidenceValue::from("jane")));
+    }
+
+    #[tokio::test]
+    async fn resolves_and_events_use_their_own_rules() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .withf(|_, _, context, _| !context.contains_key("email") && context.contains_key("bio"))
+            .times(1)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(ResolvedFlags::default()) }));
+        let server = TestServer::start(vec![("200 OK", "{}")]).await;
+        let confidence = Confidence::builder()
+            .api_config(APIConfig {
+                region: Region::Custom {
+                    resolver_url: "http://localhost:1".to_string(),
+                    events_url: server.url.clone(),
+                },
+                ..Default::default()
+            })
+            .resolver(Arc::new(mock_resolver))
+            .context(context())
+            .resolve_redaction(RedactionRules::new().rule("email", Redaction::Drop))
+            .event_redaction(RedactionRules::new().rule("bio", Redaction::Drop))
+            .build();
+
+        confidence.resolve_all().await.unwrap();
+        confidence.track(
+            "navigate",
+            HashMap::from([("bio".to_string(), ConfidenceValue::from("likes short walks"))]),
+        );
+        confidence.flush_events().await;
+
+        let body = &server.received()[0].body;
+        assert!(body.contains("jane@example.com"));
+        assert!(!body.contains("walks"));
+    }
+}
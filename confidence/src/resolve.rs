@@ -1,34 +1,52 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use chrono::Utc;
 use mockall::automock;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use tokio::time::Instant;
 use serde_json::Value;
 use crate::confidence_value::ConfidenceValue;
 use crate::models::APIConfig;
+use crate::models::AppliedFlag;
+use crate::models::ApplyRequest;
 use crate::models::NetworkResolvedFlags;
 use crate::models::ResolveError;
 use crate::models::ResolveRequest;
 use crate::models::ResolvedFlags;
 use crate::models::APIURL;
 use crate::models::SDK;
+use crate::property_path;
 use crate::{get_sdk_version, SDK_ID};
 use crate::conversion_trait::ToSerdeValueConverter;
 
+/// Resolves flags against the Confidence resolver API.
+///
+/// The resolver keeps one HTTP client for its whole lifetime, so connections are pooled
+/// across requests.
 #[derive(Clone, Default)]
-pub struct ConfidenceResolver;
+pub struct ConfidenceResolver {
+    client: reqwest::Client,
+}
 
 impl ConfidenceResolver {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
 
     async fn make_request(
         &self,
         config: &APIConfig,
         flags: Vec<String>,
         _evaluation_context: &HashMap<String, ConfidenceValue>,
+        apply: bool,
     ) -> Result<NetworkResolvedFlags, ResolveError> {
-        let flags: Vec<String> = flags.into_iter().filter_map(|flag| {
-            let flag_name: Vec<&str> = flag.split(".").collect();
-            flag_name.first().map(|name| format!("flags/{}", name))
-        }).collect();
+        let flags: Vec<String> = flags
+            .iter()
+            .map(|flag| format!("flags/{}", property_path::flag_name(flag).unwrap_or_else(|| flag.clone())))
+            .collect();
 
         let context: HashMap<String, Value> = _evaluation_context
             .iter()
@@ -40,7 +58,7 @@ impl ConfidenceResolver {
         let resolve_request = &ResolveRequest::builder()
         .client_secret(config.api_key.clone())
         .evaluation_context(context)
-        .apply(true)
+        .apply(apply)
         .sdk(sdk)
         .flags(flags)
         .build();
@@ -50,14 +68,10 @@ impl ConfidenceResolver {
             Err(_) => return Err(ResolveError::SerializationError),
         };
 
-        let client = reqwest::Client::new();
-        let response = client
-            .post(format!("{}/v1/flags:resolve", config.region.url()))
-            .header("Content-Type", "application/json")
-            .header("Accept", "application/json")
-            .body(body)
-            .send()
+        let response = self
+            .post(config, format!("{}/v1/flags:resolve", config.region.url()), body)
             .await?;
+        let response = check_status(response).await?;
 
         match response.text().await {
             Ok(body) => {
@@ -71,20 +85,152 @@ impl ConfidenceResolver {
                     }
                 }
             }
-            Err(err) => Err(ResolveError::NetworkError(err)),
+            Err(err) => Err(err.into()),
         }
     }
+
+    async fn make_apply_request(
+        &self,
+        config: &APIConfig,
+        resolve_token: &str,
+        flags: Vec<AppliedFlag>,
+    ) -> Result<(), ResolveError> {
+        let sdk = SDK::builder().id(SDK_ID).version(get_sdk_version()).build();
+
+        let apply_request = &ApplyRequest::builder()
+            .client_secret(config.api_key.clone())
+            .resolve_token(resolve_token)
+            .flags(flags)
+            .send_time(Utc::now())
+            .sdk(sdk)
+            .build();
+
+        let body = match serde_json::to_string(apply_request) {
+            Ok(json) => json,
+            Err(_) => return Err(ResolveError::SerializationError),
+        };
+
+        let response = self
+            .post(config, format!("{}/v1/flags:apply", config.region.url()), body)
+            .await?;
+        check_status(response).await?;
+        Ok(())
+    }
+
+    /// Post `body` to `url`, retrying according to the config's retry policy.
+    ///
+    /// Connection failures and `5xx` responses are retried with backoff, `429` responses only
+    /// when they carry a `Retry-After` delay no longer than `max_backoff`. All attempts together
+    /// stay within the config's timeout: the last response or error is returned as soon as another
+    /// wait would exceed it.
+    async fn post(
+        &self,
+        config: &APIConfig,
+        url: String,
+        body: String,
+    ) -> Result<reqwest::Response, ResolveError> {
+        let deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+        let mut attempt = 0;
+        loop {
+            let mut request = self
+                .client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .header("Accept", "application/json")
+                .body(body.clone());
+            if let Some(deadline) = deadline {
+                request = request.timeout(deadline.saturating_duration_since(Instant::now()));
+            }
+
+            let result = request.send().await;
+            let retry_delay = match &result {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    retry_after(response).filter(|delay| *delay <= config.retry_policy.max_backoff)
+                }
+                Ok(response) if response.status().is_server_error() => Some(config.retry_policy.backoff(attempt)),
+                Ok(_) => None,
+                Err(err) if is_retryable_error(err) => Some(config.retry_policy.backoff(attempt)),
+                Err(_) => None,
+            };
+            let delay = match retry_delay {
+                Some(delay) if attempt < config.retry_policy.max_retries
+                    && deadline.is_none_or(|deadline| Instant::now() + delay < deadline) => delay,
+                _ => return Ok(result?),
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Turn a non-success response into the matching [`ResolveError`].
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, ResolveError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(ResolveError::from_status(status.as_u16(), &body))
+}
+
+fn is_retryable_error(err: &reqwest::Error) -> bool {
+    (err.is_connect() || err.is_request()) && !err.is_timeout()
+}
+
+/// The delay requested through a `Retry-After` header given in seconds.
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
 }
 
 #[async_trait]
 #[automock]
 pub trait NetworkFlagResolver {
+    /// Resolve `flags`, reporting them as applied right away.
     async fn resolve(
         &self,
         config: &APIConfig,
         flags: Vec<String>,
         evaluation_context: &HashMap<String, ConfidenceValue>,
     ) -> Result<ResolvedFlags, ResolveError>;
+
+    /// Resolve `flags`, leaving them to be reported through [`NetworkFlagResolver::apply`] when
+    /// `apply` is false.
+    ///
+    /// The default ignores `apply` and calls [`NetworkFlagResolver::resolve`], which suits resolvers
+    /// without a separate apply step.
+    async fn resolve_with_apply(
+        &self,
+        config: &APIConfig,
+        flags: Vec<String>,
+        evaluation_context: &HashMap<String, ConfidenceValue>,
+        apply: bool,
+    ) -> Result<ResolvedFlags, ResolveError> {
+        let _ = apply;
+        self.resolve(config, flags, evaluation_context).await
+    }
+
+    /// Report flags from the resolve identified by `resolve_token` as applied.
+    ///
+    /// The default does nothing, matching the default of [`NetworkFlagResolver::resolve_with_apply`],
+    /// which has already applied every flag it resolved.
+    async fn apply(
+        &self,
+        config: &APIConfig,
+        resolve_token: &str,
+        flags: Vec<AppliedFlag>,
+    ) -> Result<(), ResolveError> {
+        let _ = (config, resolve_token, flags);
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -95,7 +241,147 @@ impl NetworkFlagResolver for ConfidenceResolver {
         flags: Vec<String>,
         evaluation_context: &HashMap<String, ConfidenceValue>,
     ) -> Result<ResolvedFlags, ResolveError> {
-        let network_response = self.make_request(config, flags, evaluation_context).await?;
-        Ok(network_response.into())
+        self.resolve_with_apply(config, flags, evaluation_context, true).await
     }
-}
\ No newline at end of file
+
+    async fn resolve_with_apply(
+        &self,
+        config: &APIConfig,
+        flags: Vec<String>,
+        evaluation_context: &HashMap<String, ConfidenceValue>,
+        apply: bool,
+    ) -> Result<ResolvedFlags, ResolveError> {
+        let network_response = self.make_request(config, flags, evaluation_context, apply).await?;
+        network_response.into_resolved_flags(config.extra_fields)
+    }
+
+    async fn apply(
+        &self,
+        config: &APIConfig,
+        resolve_token: &str,
+        flags: Vec<AppliedFlag>,
+    ) -> Result<(), ResolveError> {
+        self.make_apply_request(config, resolve_token, flags).await
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::models::{ResolveError, RetryPolicy};
+    use crate::resolve::{ConfidenceResolver, NetworkFlagResolver};
+    use crate::test_server::TestServer;
+    use crate::{APIConfig, Region};
+
+    const RESOLVE_RESPONSE: &str = r#"{
+        "resolvedFlags": [{
+            "flag": "flags/flag",
+            "variant": "flags/flag/variants/on",
+            "value": { "enabled": true },
+            "flagSchema": { "schema": { "enabled": { "boolSchema": {} } } },
+            "reason": "RESOLVE_REASON_MATCH"
+        }],
+        "resolveToken": "token"
+    }"#;
+
+    fn config(server: &TestServer) -> APIConfig {
+        APIConfig {
+            api_key: "secret".to_string(),
+            region: Region::Custom {
+                resolver_url: server.url.clone(),
+                events_url: server.url.clone(),
+            },
+            retry_policy: RetryPolicy::builder()
+                .initial_backoff(Duration::from_millis(10))
+                .max_backoff(Duration::from_millis(20))
+                .build(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_against_custom_url_after_retrying_server_error() {
+        let server = TestServer::start(vec![("503 Service Unavailable", ""), ("200 OK", RESOLVE_RESPONSE)]).await;
+
+        let resolved = ConfidenceResolver::default()
+            .resolve(&config(&server), vec!["flag".to_string()], &HashMap::new())
+            .await
+            .unwrap();
+
+        assert_eq!(resolved.resolve_token, "token");
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[1].path, "/v1/flags:resolve");
+        assert!(received[1].body.contains("flags/flag"));
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = TestServer::start(vec![("400 Bad Request", "{}")]).await;
+
+        let result = ConfidenceResolver::default()
+            .resolve(&config(&server), vec!["flag".to_string()], &HashMap::new())
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejected_client_secret_is_reported() {
+        let server = TestServer::start(vec![(
+            "401 Unauthorized",
+            r#"{"code": 16, "message": "client secret not found"}"#,
+        )])
+        .await;
+
+        let result = ConfidenceResolver::default()
+            .resolve(&config(&server), vec!["flag".to_string()], &HashMap::new())
+            .await;
+
+        match result {
+            Err(ResolveError::Unauthorized { status, error }) => {
+                assert_eq!(status, 401);
+                assert_eq!(error.unwrap().message, "client secret not found");
+            }
+            other => panic!("expected an unauthorized error, got {:?}", other.err()),
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_after_beyond_max_backoff_is_not_awaited() {
+        let server = TestServer::start(vec![("429 Too Many Requests\r\nRetry-After: 3600", "")]).await;
+        let config = APIConfig {
+            timeout: None,
+            ..config(&server)
+        };
+
+        let started = std::time::Instant::now();
+        let result = ConfidenceResolver::default()
+            .resolve(&config, vec!["flag".to_string()], &HashMap::new())
+            .await;
+
+        assert_eq!(result.unwrap_err().status(), Some(429));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_after_beyond_the_deadline_is_not_awaited() {
+        let server = TestServer::start(vec![("429 Too Many Requests\r\nRetry-After: 5", "")]).await;
+        let config = APIConfig {
+            timeout: Some(Duration::from_millis(500)),
+            ..config(&server)
+        };
+
+        let started = std::time::Instant::now();
+        let result = ConfidenceResolver::default()
+            .resolve(&config, vec!["flag".to_string()], &HashMap::new())
+            .await;
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.received().len(), 1);
+    }
+}
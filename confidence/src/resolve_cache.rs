@@ -0,0 +1,347 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use lru::LruCache;
+use tokio::time::Instant;
+use typed_builder::TypedBuilder;
+
+use crate::models::ResolvedFlags;
+use crate::property_path;
+use crate::ConfidenceValue;
+
+/// An in-process cache of resolved flags, keyed by the requested flags and the evaluation context.
+///
+/// Entries expire after `ttl`. Once `max_entries` is reached, the least recently used entry is
+/// evicted to make room for a new one.
+#[derive(TypedBuilder, Debug)]
+pub struct ResolveCache {
+    #[builder(default = Duration::from_secs(60))]
+    ttl: Duration,
+    #[builder(default = 1000)]
+    max_entries: usize,
+    #[builder(default, setter(skip))]
+    state: Mutex<CacheState>,
+    // Two independently seeded hashers, combined into a 128-bit context fingerprint.
+    #[builder(default, setter(skip))]
+    hashers: (RandomState, RandomState),
+}
+
+#[derive(Debug)]
+struct CacheState {
+    entries: LruCache<CacheKey, CacheEntry>,
+}
+
+impl Default for CacheState {
+    fn default() -> Self {
+        CacheState {
+            entries: LruCache::unbounded(),
+        }
+    }
+}
+
+/// Identifies a cache entry; build it once per resolve with [`ResolveCache::key`].
+#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+pub(crate) struct CacheKey {
+    flags: Vec<String>,
+    context: u128,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    resolved_flags: ResolvedFlags,
+    inserted_at: Instant,
+}
+
+/// Feed `value` to `hasher` so that equal values hash equally, whatever the order of their keys.
+fn hash_value<H: Hasher>(value: &ConfidenceValue, hasher: &mut H) {
+    std::mem::discriminant(value).hash(hasher);
+    match value {
+        ConfidenceValue::Bool(value) => value.hash(hasher),
+        ConfidenceValue::Int(value) => value.hash(hasher),
+        ConfidenceValue::Float(value) => value.to_bits().hash(hasher),
+        ConfidenceValue::String(value) => value.hash(hasher),
+        ConfidenceValue::Array(values) => {
+            values.len().hash(hasher);
+            for value in values {
+                hash_value(value, hasher);
+            }
+        }
+        ConfidenceValue::Struct(value) => hash_fields(value.fields.iter(), hasher),
+        ConfidenceValue::Timestamp(value) => value.hash(hasher),
+        ConfidenceValue::Date(value) => value.hash(hasher),
+        ConfidenceValue::Null => {}
+    }
+}
+
+fn hash_fields<'a, H: Hasher>(fields: impl Iterator<Item = (&'a String, &'a ConfidenceValue)>, hasher: &mut H) {
+    let mut fields: Vec<_> = fields.collect();
+    fields.sort_unstable_by_key(|(key, _)| *key);
+    fields.len().hash(hasher);
+    for (key, value) in fields {
+        key.hash(hasher);
+        hash_value(value, hasher);
+    }
+}
+
+impl ResolveCache {
+    fn context_fingerprint(&self, context: &HashMap<String, ConfidenceValue>) -> u128 {
+        let mut high = self.hashers.0.build_hasher();
+        let mut low = self.hashers.1.build_hasher();
+        hash_fields(context.iter(), &mut high);
+        hash_fields(context.iter(), &mut low);
+        (u128::from(high.finish()) << 64) | u128::from(low.finish())
+    }
+
+    pub(crate) fn key(&self, flags: &[String], context: &HashMap<String, ConfidenceValue>) -> CacheKey {
+        let mut flags: Vec<String> = flags
+            .iter()
+            .map(|flag| property_path::flag_name(flag).unwrap_or_else(|| flag.clone()))
+            .collect();
+        flags.sort();
+        flags.dedup();
+        CacheKey {
+            flags,
+            context: self.context_fingerprint(context),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &CacheKey) -> Option<ResolvedFlags> {
+        let mut state = self.state.lock().unwrap();
+        match state.entries.get(key) {
+            Some(entry) if entry.inserted_at.elapsed() < self.ttl => return Some(entry.resolved_flags.clone()),
+            Some(_) => {}
+            None => return None,
+        }
+        state.entries.pop(key);
+        None
+    }
+
+    pub(crate) fn insert(&self, key: CacheKey, resolved_flags: ResolvedFlags) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if !state.entries.contains(&key) && state.entries.len() >= self.max_entries {
+            state.entries.pop_lru();
+        }
+        state.entries.put(
+            key,
+            CacheEntry {
+                resolved_flags,
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Drop every cached entry resolved for `context`, as sent to the resolver.
+    pub(crate) fn invalidate(&self, context: &HashMap<String, ConfidenceValue>) {
+        let context = self.context_fingerprint(context);
+        let mut state = self.state.lock().unwrap();
+        let stale: Vec<CacheKey> = state
+            .entries
+            .iter()
+            .filter(|(key, _)| key.context == context)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            state.entries.pop(&key);
+        }
+    }
+
+    /// Drop every cached entry.
+    pub fn invalidate_all(&self) {
+        self.state.lock().unwrap().entries.clear();
+    }
+
+    /// The number of entries currently held, including expired ones not yet evicted.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::details::EvaluationReason;
+    use crate::models::{ResolvedFlag, ResolvedFlags};
+    use crate::resolve::MockNetworkFlagResolver;
+    use crate::resolve_cache::ResolveCache;
+    use crate::{APIConfig, Confidence, ConfidenceValue, Region};
+    use crate::confidence_value::StructValue;
+    use crate::context_provider::ContextProvider;
+    use crate::contextual_confidence::Contextual;
+    use crate::redaction::{Redaction, RedactionRules};
+
+    fn resolved_flags(flag: &str) -> ResolvedFlags {
+        ResolvedFlags {
+            resolve_token: "token".to_string(),
+            flags: vec![ResolvedFlag {
+                flag: format!("flags/{flag}"),
+                variant: format!("flags/{flag}/variants/on"),
+                value: Some(StructValue::default().with_field("enabled", true)),
+                reason: "RESOLVE_REASON_MATCH".to_string(),
+            }],
+        }
+    }
+
+    fn context(user: &str) -> HashMap<String, ConfidenceValue> {
+        HashMap::from([("user_id".to_string(), ConfidenceValue::from(user))])
+    }
+
+    #[tokio::test]
+    async fn get_flag_is_served_from_cache() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .times(1)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolved_flags("flag")) }));
+
+        let confidence = Confidence::builder()
+            .api_config(APIConfig { api_key: "".to_string(), region: Region::Global, ..Default::default() })
+            .resolver(Arc::new(mock_resolver))
+            .cache(ResolveCache::builder().build())
+            .build();
+
+        let first = confidence.get_flag("flag.enabled", false).await.unwrap();
+        let second = confidence.get_flag("flag.enabled", false).await.unwrap();
+
+        assert_eq!(first.reason, Some(EvaluationReason::TargetingMatch));
+        assert_eq!(second.reason, Some(EvaluationReason::Cached));
+        assert!(second.value);
+    }
+
+    #[tokio::test]
+    async fn cache_is_keyed_by_context() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .times(2)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolved_flags("flag")) }));
+
+        let confidence = Confidence::builder()
+            .api_config(APIConfig { api_key: "".to_string(), region: Region::Global, ..Default::default() })
+            .resolver(Arc::new(mock_resolver))
+            .cache(ResolveCache::builder().build())
+            .build();
+
+        let alice = confidence.with_context(context("alice"));
+        let bob = confidence.with_context(context("bob"));
+        alice.get_flag("flag.enabled", false).await.unwrap();
+        bob.get_flag("flag.enabled", false).await.unwrap();
+        let details = alice.get_flag("flag.enabled", false).await.unwrap();
+
+        assert_eq!(details.reason, Some(EvaluationReason::Cached));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn entries_expire_after_ttl() {
+        let cache = ResolveCache::builder().ttl(Duration::from_secs(10)).build();
+        let flags = vec!["flag".to_string()];
+        cache.insert(cache.key(&flags, &context("alice")), resolved_flags("flag"));
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(cache.get(&cache.key(&flags, &context("alice"))).is_some());
+
+        tokio::time::advance(Duration::from_secs(6)).await;
+        assert!(cache.get(&cache.key(&flags, &context("alice"))).is_none());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        let cache = ResolveCache::builder().max_entries(2).build();
+        let flags = vec!["flag".to_string()];
+        cache.insert(cache.key(&flags, &context("alice")), resolved_flags("flag"));
+        cache.insert(cache.key(&flags, &context("bob")), resolved_flags("flag"));
+        cache.get(&cache.key(&flags, &context("alice")));
+        cache.insert(cache.key(&flags, &context("carol")), resolved_flags("flag"));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&cache.key(&flags, &context("alice"))).is_some());
+        assert!(cache.get(&cache.key(&flags, &context("bob"))).is_none());
+    }
+
+    #[test]
+    fn property_paths_share_an_entry() {
+        let cache = ResolveCache::builder().build();
+        cache.insert(cache.key(&["flag.a".to_string()], &context("alice")), resolved_flags("flag"));
+
+        assert!(cache.get(&cache.key(&["flag.b".to_string()], &context("alice"))).is_some());
+    }
+
+    #[test]
+    fn invalidation() {
+        let cache = ResolveCache::builder().build();
+        let flags = vec!["flag".to_string()];
+        cache.insert(cache.key(&flags, &context("alice")), resolved_flags("flag"));
+        cache.insert(cache.key(&flags, &context("bob")), resolved_flags("flag"));
+
+        cache.invalidate(&context("alice"));
+        assert!(cache.get(&cache.key(&flags, &context("alice"))).is_none());
+        assert!(cache.get(&cache.key(&flags, &context("bob"))).is_some());
+
+        cache.invalidate_all();
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalidating_one_context_keeps_the_others() {
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver
+            .expect_resolve_with_apply()
+            .times(3)
+            .returning(|_, _, _, _| Box::pin(async move { Ok(resolved_flags("flag")) }));
+        let confidence = Confidence::builder()
+            .api_config(APIConfig { api_key: "".to_string(), region: Region::Global, ..Default::default() })
+            .resolver(Arc::new(mock_resolver))
+            .context(HashMap::from([("app".to_string(), ConfidenceValue::from("shop"))]))
+            .context_providers(vec![Arc::new(|| context("ignored")) as Arc<dyn ContextProvider>])
+            .resolve_redaction(RedactionRules::new().rule("email", Redaction::Drop))
+            .cache(ResolveCache::builder().build())
+            .build();
+        let mut alice_context = context("alice");
+        alice_context.insert("email".to_string(), ConfidenceValue::from("alice@example.com"));
+        let alice = confidence.with_context(alice_context.clone());
+        let bob = confidence.with_context(context("bob"));
+        alice.get_flag("flag.enabled", false).await.unwrap();
+        bob.get_flag("flag.enabled", false).await.unwrap();
+
+        confidence.invalidate_cache_for(alice_context);
+
+        let alice_details = alice.get_flag("flag.enabled", false).await.unwrap();
+        let bob_details = bob.get_flag("flag.enabled", false).await.unwrap();
+        assert_eq!(alice_details.reason, Some(EvaluationReason::TargetingMatch));
+        assert_eq!(bob_details.reason, Some(EvaluationReason::Cached));
+    }
+
+    #[test]
+    fn keys_depend_on_context_content_only() {
+        let cache = ResolveCache::builder().build();
+        let flags = vec!["flag".to_string()];
+        let nested = |first: &str, second: &str| {
+            let mut context = context("alice");
+            context.insert(
+                "device".to_string(),
+                StructValue::default().with_field(first, 1).with_field(second, 2).into(),
+            );
+            context
+        };
+
+        assert_eq!(cache.key(&flags, &nested("a", "b")), cache.key(&flags, &nested("a", "b")));
+        assert_ne!(cache.key(&flags, &nested("a", "b")), cache.key(&flags, &nested("b", "a")));
+        assert_ne!(
+            cache.key(&flags, &context("alice")),
+            cache.key(&flags, &HashMap::from([("user_id".to_string(), ConfidenceValue::from(1.0))]))
+        );
+    }
+}
@@ -96,9 +96,32 @@ mod tests {
         let config = APIConfig {
             api_key: "test_key".to_string(),
             region: Region::Global,
+            ..Default::default()
         };
         
         assert_eq!(config.api_key, "test_key");
         assert_eq!(config.region, Region::Global);
     }
+
+    #[tokio::test]
+    async fn test_resolve_errors_keep_their_error_code() {
+        use std::sync::Arc;
+        use crate::models::ResolveError;
+        use crate::resolve::MockNetworkFlagResolver;
+        use crate::Confidence;
+
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver.expect_resolve_with_apply().returning(|_, _, _, _| {
+            Box::pin(async move { Err(ResolveError::InvalidArgument { status: 400, error: None }) })
+        });
+        let confidence = Confidence::builder()
+            .api_config(APIConfig { api_key: "test_key".to_string(), ..Default::default() })
+            .resolver(Arc::new(mock_resolver))
+            .build();
+
+        let error = confidence.get_flag("test.flag", false).await.unwrap_err();
+
+        assert_eq!(error.code, EvaluationErrorCode::InvalidContext);
+        assert!(error.message.unwrap().contains("HTTP 400"));
+    }
 }
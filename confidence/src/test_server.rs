@@ -0,0 +1,100 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A request received by a [`TestServer`].
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    pub path: String,
+    /// The request line and headers.
+    pub head: String,
+    pub body: String,
+}
+
+/// A plain-HTTP server on localhost that answers requests with canned responses in order,
+/// repeating the last one once the list runs out.
+pub struct TestServer {
+    pub url: String,
+    pub requests: Arc<Mutex<Vec<ReceivedRequest>>>,
+}
+
+impl TestServer {
+    /// Each response is a status line and headers, e.g. `"503 Service Unavailable"`,
+    /// paired with its body.
+    pub async fn start(responses: Vec<(&'static str, &'static str)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Arc<Mutex<Vec<ReceivedRequest>>> = Arc::default();
+        let received = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            let mut index = 0;
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let Some(request) = read_request(&mut socket).await else {
+                    continue;
+                };
+                received.lock().unwrap().push(request);
+
+                let (status, body) = responses[index.min(responses.len() - 1)];
+                index += 1;
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        TestServer { url, requests }
+    }
+
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Option<ReceivedRequest> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let header_end = loop {
+        let read = socket.read(&mut buffer).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buffer[..read]);
+        if let Some(position) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let path = head.split_whitespace().nth(1)?.to_string();
+    let content_length: usize = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse().ok())
+                .flatten()
+        })
+        .unwrap_or(0);
+
+    while data.len() < header_end + content_length {
+        let read = socket.read(&mut buffer).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..read]);
+    }
+
+    Some(ReceivedRequest {
+        path,
+        head,
+        body: String::from_utf8_lossy(&data[header_end..]).to_string(),
+    })
+}
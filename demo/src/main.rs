@@ -10,6 +10,7 @@ async fn main() {
 let api_config = APIConfig {
         api_key: "API_KEY".to_string(),
         region: Region::Global,
+        ..Default::default()
     };
     let mut context = HashMap::new();
     context.insert("visitor_id".to_string(), ConfidenceValue::String("dennis".to_string()));
@@ -21,8 +22,9 @@ let api_config = APIConfig {
 
     println!("details string -> {:?}", details_string.unwrap().value);
 
-    // send 10 track events and wait 10 seconds
+    // send 10 track events and wait until they are published
     for i in 0..10 {
         confidence.track("navigate", HashMap::new());
     }
+    confidence.flush_events().await;
 }
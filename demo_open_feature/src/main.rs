@@ -8,6 +8,7 @@ async fn main() {
 let api_config = APIConfig {
         api_key: "API_KEY".to_string(),
         region: Region::Global,
+        ..Default::default()
     };
     let confidence = Confidence::new(api_config);
     let provider = ConfidenceProvider::new(confidence);
@@ -16,7 +16,7 @@ pub trait ToValueConverter {
 
 impl ToValueConverter for ConfidenceStructValue {
     fn convert(self) -> StructValue {
-        let fields = self.fields.iter().map(|(key, value)| (key.clone(), value.clone().convert())).collect();
+        let fields = self.fields.iter().filter_map(|(key, value)| Some((key.clone(), value.clone().convert()?))).collect();
         StructValue {
             fields
         }
@@ -24,12 +24,13 @@ impl ToValueConverter for ConfidenceStructValue {
 }
 
 trait ToOFValueConverter {
-    fn convert(self) -> open_feature::Value;
+    /// `None` for null values, which OpenFeature has no representation for.
+    fn convert(self) -> Option<open_feature::Value>;
 }
 
 impl ToOFValueConverter for ConfidenceValue {
-    fn convert(self) -> open_feature::Value {
-        match self {
+    fn convert(self) -> Option<open_feature::Value> {
+        let value = match self {
             ConfidenceValue::Bool(value) => {
                 open_feature::Value::Bool(value)
             }
@@ -45,25 +46,25 @@ impl ToOFValueConverter for ConfidenceValue {
             ConfidenceValue::Array(value) => {
                 let list = value
                     .iter()
-                    .map(|item| item.clone().convert() )
+                    .filter_map(|item| item.clone().convert() )
                     .collect();
 
                 open_feature::Value::Array(list)
             }
             ConfidenceValue::Struct(struct_value) => {
-                let fields = struct_value
-                    .fields
-                    .iter()
-                    .map(|(key, value)| (key.clone(), value.clone().convert()))
-                    .collect();
-
-                open_feature::Value::Struct(
-                    StructValue {
-                    fields
-                })
-
+                open_feature::Value::Struct(struct_value.convert())
             }
-        }
+            ConfidenceValue::Timestamp(value) => {
+                open_feature::Value::String(value.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true))
+            }
+            ConfidenceValue::Date(value) => {
+                open_feature::Value::String(value.format("%Y-%m-%d").to_string())
+            }
+            ConfidenceValue::Null => {
+                return None;
+            }
+        };
+        Some(value)
     }
 }
 
@@ -161,7 +162,7 @@ impl ReasonConverter for Option<ConfidenceReason> {
                     open_feature::EvaluationReason::Cached
                 }
                 EvaluationReason::Disabled => {
-                    open_feature::EvaluationReason::Cached
+                    open_feature::EvaluationReason::Disabled
                 }
                 EvaluationReason::Unknown => {
                     open_feature::EvaluationReason::Unknown
@@ -215,7 +216,10 @@ impl EvaluationContextValueConverter for EvaluationContextFieldValue {
                 ConfidenceValue::String(value)
             }
             EvaluationContextFieldValue::DateTime(value) => {
-                ConfidenceValue::String(value.to_string())
+                match chrono::DateTime::from_timestamp(value.unix_timestamp(), value.nanosecond()) {
+                    Some(timestamp) => ConfidenceValue::Timestamp(timestamp),
+                    None => ConfidenceValue::String(value.to_string()),
+                }
             }
             EvaluationContextFieldValue::Struct(value) => {
                 let of_struct_value =  value.clone().downcast::<StructValue>().unwrap();
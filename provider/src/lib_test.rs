@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
 
     use serde_json;
     use open_feature::{EvaluationContext, OpenFeature, StructValue};
@@ -30,10 +30,11 @@ mod tests {
         let api_config = APIConfig {
             api_key: "".to_string(),
             region: Region::Global,
+            ..Default::default()
         };
 
         let mut mock_resolver = MockNetworkFlagResolver::new();
-        mock_resolver.expect_resolve().returning(|_, _, _| {
+        mock_resolver.expect_resolve_with_apply().returning(|_, _, _, _| {
             Box::pin(async move { resolve_response("test-flag".to_string()) })
         });
 
@@ -150,6 +151,91 @@ mod tests {
         assert_eq!(value, true);
     }
 
+    #[tokio::test]
+    async fn resolve_details_with_metadata() {
+        let context = EvaluationContext {
+            targeting_key: Some("3poj234lknwfklnasflkaflakjlkejelkfjlkj".to_string()),
+            custom_fields: HashMap::new()
+        };
+
+        let client = setup_provider().await;
+
+        let details = client
+            .get_bool_details("test-flag.boolean-key", Some(&context), None)
+            .await
+            .unwrap();
+
+        assert_eq!(details.reason, Some(open_feature::EvaluationReason::TargetingMatch));
+        assert_eq!(
+            details.flag_metadata.values["flag"],
+            open_feature::FlagMetadataValue::String("flags/test-flag".to_string())
+        );
+        assert_eq!(
+            details.flag_metadata.values["resolve_reason"],
+            open_feature::FlagMetadataValue::String("RESOLVE_REASON_MATCH".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn evaluation_context_is_sent_to_the_resolver() {
+        use open_feature::provider::FeatureProvider;
+        use spotify_confidence_sdk::contextual_confidence::Contextual;
+        use spotify_confidence_sdk::ConfidenceValue;
+
+        type SentContexts = Arc<Mutex<Vec<HashMap<String, ConfidenceValue>>>>;
+
+        let sent: SentContexts = Arc::default();
+        let recorded = Arc::clone(&sent);
+        let mut mock_resolver = MockNetworkFlagResolver::new();
+        mock_resolver.expect_resolve_with_apply().returning(move |_, _, context, _| {
+            recorded.lock().unwrap().push(context.clone());
+            Box::pin(async move { resolve_response("test-flag".to_string()) })
+        });
+        let confidence = Confidence::builder()
+            .api_config(APIConfig::default())
+            .resolver(Arc::new(mock_resolver))
+            .build()
+            .with_context(HashMap::from([("app".to_string(), ConfidenceValue::from("shop"))]));
+        let provider = ConfidenceProvider::new(confidence);
+
+        let anonymous = EvaluationContext::default().with_custom_field("country", "SE");
+        let signed_in = EvaluationContext::default().with_targeting_key("user-1");
+        provider.resolve_bool_value("test-flag.boolean-key", &anonymous).await.unwrap();
+        provider.resolve_bool_value("test-flag.boolean-key", &signed_in).await.unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(
+            sent[0],
+            HashMap::from([
+                ("app".to_string(), ConfidenceValue::from("shop")),
+                ("country".to_string(), ConfidenceValue::from("SE")),
+            ])
+        );
+        assert_eq!(
+            sent[1],
+            HashMap::from([
+                ("app".to_string(), ConfidenceValue::from("shop")),
+                ("targeting_key".to_string(), ConfidenceValue::from("user-1")),
+            ])
+        );
+        assert!(Confidence::new(APIConfig::default()).get_context().is_empty());
+    }
+
+    #[test]
+    fn date_time_context_is_sent_as_timestamp() {
+        use open_feature::EvaluationContextFieldValue;
+        use spotify_confidence_sdk::ConfidenceValue;
+        use crate::conversion_traits::EvaluationContextValueConverter;
+
+        let signed_up = time::OffsetDateTime::from_unix_timestamp(1714566600).unwrap();
+        let value = EvaluationContextFieldValue::DateTime(signed_up).convert();
+
+        assert_eq!(
+            value,
+            ConfidenceValue::Timestamp(chrono::DateTime::from_timestamp(1714566600, 0).unwrap())
+        );
+    }
+
     fn resolve_response(flag: String) -> Result<ResolvedFlags, ResolveError> {
         let json_data = r#"
         {
@@ -224,6 +310,6 @@ mod tests {
             .replace("{flag}", flag.as_ref());
 
         let network_flags: NetworkResolvedFlags = serde_json::from_str(&json_data).unwrap();
-        return Ok(network_flags.into());
+        return network_flags.try_into();
     }
 }
\ No newline at end of file